@@ -0,0 +1,160 @@
+use near_contract_standards::fungible_token::core::FungibleTokenCore;
+use near_contract_standards::fungible_token::metadata::{FungibleTokenMetadata, FungibleTokenMetadataProvider};
+use near_contract_standards::fungible_token::resolver::FungibleTokenResolver;
+use near_contract_standards::storage_management::StorageManagement;
+use near_sdk::env;
+use near_sdk::json_types::{Base64VecU8, U128};
+use near_sdk::AccountId;
+use rust_near_sokoban::auxiliary::{Direction, HexDirection, Size};
+use rust_near_sokoban::board::BoardOptions;
+use rust_near_sokoban::game::TurnOrder;
+use rust_near_sokoban::moderation::{ModerationAction, ModerationReason};
+use rust_near_sokoban::svg::Theme;
+use rust_near_sokoban::{BoardIndex, GameIndex};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::HostedContract;
+
+fn arg<T: DeserializeOwned>(args: &Value, name: &str) -> Result<T, String> {
+    let value = args.get(name).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| format!("Invalid argument {}: {}", name, e))
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+/// Deserializes the arguments of `$method` from JSON and calls it on `$contract`.
+/// Methods marked with `-> promise` return a `Promise` and produce `null`.
+macro_rules! dispatch {
+    ($contract:ident, $method:expr, $args:expr, {
+        $( $name:ident ( $( $arg:ident : $ty:ty ),* ) $( -> $kind:ident )? ; )*
+    }) => {
+        match $method {
+            $( stringify!($name) => {
+                $( let $arg: $ty = arg($args, stringify!($arg))?; )*
+                dispatch!(@ret $contract.$name($( $arg ),*) $(, $kind)?)
+            }, )*
+            _ => Err(format!("Unknown method {}", $method)),
+        }
+    };
+    (@ret $call:expr) => { to_value($call) };
+    (@ret $call:expr, promise) => {{ $call; Ok(Value::Null) }};
+}
+
+fn read_state<T: near_sdk::borsh::BorshDeserialize>() -> Result<T, String> {
+    env::state_read().ok_or_else(|| String::from("The contract is not initialized"))
+}
+
+pub struct SokobanHost;
+
+impl HostedContract for SokobanHost {
+    fn call(&self, method: &str, args: &Value) -> Result<Value, String> {
+        match method {
+            "new" => {
+                env::state_write(&rust_near_sokoban::Contract::new());
+                return Ok(Value::Null);
+            },
+            "migrate" => {
+                env::state_write(&rust_near_sokoban::Contract::migrate());
+                return Ok(Value::Null);
+            },
+            _ => (),
+        }
+
+        let mut contract: rust_near_sokoban::Contract = read_state()?;
+        let result = dispatch!(contract, method, args, {
+            create_board(field: Base64VecU8, field_size: Option<Size>, options: Option<BoardOptions>);
+            get_board(index: BoardIndex);
+            validate_board(index: BoardIndex);
+            create_single_game(index: BoardIndex, player: AccountId);
+            create_cooperative_game(index: BoardIndex, player: AccountId, partner: AccountId, turn_order: TurnOrder);
+            get_single_game(index: GameIndex);
+            get_single_game_board(index: GameIndex);
+            get_board_svg(index: BoardIndex, theme: Option<Theme>);
+            get_game_svg(index: GameIndex, theme: Option<Theme>);
+            start_single_game(index: GameIndex);
+            step(index: GameIndex, direction: Direction);
+            steps(index: GameIndex, directions: Vec<Direction>);
+            agent_steps(index: GameIndex, agent: u8, directions: Vec<Direction>);
+            hex_step(index: GameIndex, direction: HexDirection);
+            hex_steps(index: GameIndex, directions: Vec<HexDirection>);
+            agent_hex_steps(index: GameIndex, agent: u8, directions: Vec<HexDirection>);
+            delete_game(index: GameIndex);
+            delete_board(index: BoardIndex);
+            create_wagered_game(index: BoardIndex, player: AccountId);
+            accept_wager(index: GameIndex);
+            claim_timeout(index: GameIndex);
+            get_escrow(index: GameIndex);
+            get_unclaimed(account_id: AccountId);
+            withdraw_unclaimed() -> promise;
+            on_escrow_transfer(account_id: AccountId, amount: U128);
+            storage_deposit(account_id: Option<AccountId>, registration_only: Option<bool>);
+            storage_withdraw(amount: Option<U128>);
+            storage_unregister(force: Option<bool>);
+            storage_balance_bounds();
+            storage_balance_of(account_id: AccountId);
+            get_owner();
+            migrate_step(limit: u64);
+            add_moderator(account_id: AccountId);
+            remove_moderator(account_id: AccountId);
+            get_moderators();
+            is_moderator(account_id: AccountId);
+            moderate_board(index: BoardIndex, action: ModerationAction, reason: ModerationReason);
+            get_board_visibility(index: BoardIndex);
+            get_boards(from_index: Option<BoardIndex>, limit: Option<u64>);
+            get_moderation_log(from_index: Option<u64>, limit: Option<u64>);
+        });
+
+        env::state_write(&contract);
+        result
+    }
+}
+
+pub struct TokenHost;
+
+impl HostedContract for TokenHost {
+    fn call(&self, method: &str, args: &Value) -> Result<Value, String> {
+        match method {
+            "new_default_meta" => {
+                let contract = sb_token::Contract::new_default_meta(
+                    arg(args, "owner_id")?,
+                    arg(args, "total_supply")?,
+                );
+                env::state_write(&contract);
+                return Ok(Value::Null);
+            },
+            "new" => {
+                let metadata: FungibleTokenMetadata = arg(args, "metadata")?;
+                let contract = sb_token::Contract::new(
+                    arg(args, "owner_id")?,
+                    arg(args, "total_supply")?,
+                    metadata,
+                );
+                env::state_write(&contract);
+                return Ok(Value::Null);
+            },
+            _ => (),
+        }
+
+        let mut contract: sb_token::Contract = read_state()?;
+        let result = dispatch!(contract, method, args, {
+            ft_transfer(receiver_id: AccountId, amount: U128, memo: Option<String>);
+            ft_transfer_call(receiver_id: AccountId, amount: U128, memo: Option<String>, msg: String) -> promise;
+            ft_total_supply();
+            ft_balance_of(account_id: AccountId);
+            ft_resolve_transfer(sender_id: AccountId, receiver_id: AccountId, amount: U128);
+            ft_metadata();
+            storage_deposit(account_id: Option<AccountId>, registration_only: Option<bool>);
+            storage_withdraw(amount: Option<U128>);
+            storage_unregister(force: Option<bool>);
+            storage_balance_bounds();
+            storage_balance_of(account_id: AccountId);
+        });
+
+        env::state_write(&contract);
+        result
+    }
+}
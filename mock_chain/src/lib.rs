@@ -0,0 +1,362 @@
+//! In-process chain that hosts the sokoban and `sb_token` contracts natively.
+//!
+//! Every call runs against the contract's own storage inside a fresh
+//! `MockedBlockchain`, the same way `testing_env!` does for unit tests. Receipts
+//! created by a call are executed right after it: transfers move balances between
+//! accounts (and fail for unknown accounts), function calls are dispatched to the
+//! hosted contract. A function call back into the contract that created it is
+//! treated as a callback and receives the results of the receipts created before it.
+//!
+//! Contract panics are caught and roll back the storage of the failed call. Only
+//! std panics can be caught: `env::panic_str` aborts the process when it isn't
+//! running on wasm, so failing scenarios should rely on `require!`, `assert!` or
+//! `expect` (which panic through std in debug builds).
+
+mod hosts;
+pub mod scenario;
+
+pub use crate::hosts::*;
+
+use std::collections::HashMap;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+
+use near_sdk::mock::{with_mocked_blockchain, Receipt, VmAction};
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{
+    env, AccountId, Balance, BlockHeight, Gas, MockedBlockchain, PromiseResult,
+    RuntimeFeesConfig, StorageUsage, Timestamp, VMConfig,
+};
+use serde_json::Value;
+
+const PREPAID_GAS: Gas = Gas(300_000_000_000_000);
+
+/// A contract that can be called by method name with JSON arguments. It keeps
+/// its state under the `STATE` key, like the code generated by `near_bindgen`.
+pub trait HostedContract {
+    fn call(&self, method: &str, args: &Value) -> Result<Value, String>;
+}
+
+struct Deployment {
+    host: Box<dyn HostedContract>,
+    storage: HashMap<Vec<u8>, Vec<u8>>,
+    storage_usage: StorageUsage,
+}
+
+struct Account {
+    balance: Balance,
+    deployment: Option<Deployment>,
+}
+
+/// Result of a transaction signed by a user, including all receipts it spawned.
+pub struct Outcome {
+    pub result: Result<Value, String>,
+    pub logs: Vec<String>,
+}
+
+pub struct MockChain {
+    pub block_timestamp: Timestamp,
+    pub block_height: BlockHeight,
+    accounts: HashMap<AccountId, Account>,
+    logs: Vec<String>,
+}
+
+impl Default for MockChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockChain {
+    pub fn new() -> Self {
+        Self {
+            block_timestamp: 0,
+            block_height: 0,
+            accounts: HashMap::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    pub fn create_account(&mut self, account_id: AccountId, balance: Balance) {
+        self.accounts.insert(account_id, Account { balance, deployment: None });
+    }
+
+    /// Creates `account_id` with a contract on it. The contract still has to be
+    /// initialized by calling its init method.
+    pub fn deploy(&mut self, account_id: AccountId, host: Box<dyn HostedContract>, balance: Balance) {
+        let deployment = Deployment { host, storage: HashMap::new(), storage_usage: 0 };
+        self.accounts.insert(account_id, Account { balance, deployment: Some(deployment) });
+    }
+
+    pub fn balance(&self, account_id: &AccountId) -> Option<Balance> {
+        self.accounts.get(account_id).map(|account| account.balance)
+    }
+
+    pub fn advance_time(&mut self, nanoseconds: Timestamp) {
+        self.block_timestamp += nanoseconds;
+        self.block_height += 1;
+    }
+
+    /// Signs and executes a function call, then all receipts it created.
+    pub fn call(
+        &mut self,
+        signer: &AccountId,
+        contract: &AccountId,
+        method: &str,
+        args: Value,
+        deposit: Balance,
+    ) -> Outcome {
+        let first_log = self.logs.len();
+
+        let result = match self.accounts.get_mut(signer) {
+            Some(account) if account.balance >= deposit => {
+                account.balance -= deposit;
+                let result = self.execute(signer, signer, contract, method, &args, deposit, Vec::new());
+                if result.is_err() {
+                    self.credit(signer, deposit);
+                }
+                result
+            },
+            Some(_) => Err(format!("{} doesn't have enough balance", signer)),
+            None => Err(format!("Account {} doesn't exist", signer)),
+        };
+
+        self.advance_time(0);
+        Outcome { result, logs: self.logs[first_log..].to_vec() }
+    }
+
+    /// Calls a view method. Storage changes made by it are discarded.
+    pub fn view(&mut self, contract: &AccountId, method: &str, args: Value) -> Result<Value, String> {
+        let deployment = self.accounts
+            .get(contract)
+            .and_then(|account| account.deployment.as_ref())
+            .ok_or_else(|| format!("No contract on {}", contract))?;
+
+        let context = VMContextBuilder::new()
+            .current_account_id(contract.clone())
+            .block_timestamp(self.block_timestamp)
+            .block_index(self.block_height)
+            .storage_usage(deployment.storage_usage)
+            .build();
+        set_blockchain(context, Vec::new(), deployment.storage.clone());
+
+        catch_panic(|| deployment.host.call(method, &args))
+    }
+
+    /// Every log written since the chain was created.
+    pub fn logs(&self) -> &[String] {
+        &self.logs
+    }
+
+    fn credit(&mut self, account_id: &AccountId, amount: Balance) {
+        if let Some(account) = self.accounts.get_mut(account_id) {
+            account.balance += amount;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn execute(
+        &mut self,
+        signer: &AccountId,
+        predecessor: &AccountId,
+        receiver: &AccountId,
+        method: &str,
+        args: &Value,
+        deposit: Balance,
+        promise_results: Vec<PromiseResult>,
+    ) -> Result<Value, String> {
+        let account = self.accounts
+            .get_mut(receiver)
+            .ok_or_else(|| format!("Account {} doesn't exist", receiver))?;
+        let deployment = account.deployment
+            .as_mut()
+            .ok_or_else(|| format!("No contract on {}", receiver))?;
+
+        let context = VMContextBuilder::new()
+            .current_account_id(receiver.clone())
+            .signer_account_id(signer.clone())
+            .predecessor_account_id(predecessor.clone())
+            .attached_deposit(deposit)
+            .account_balance(account.balance)
+            .block_timestamp(self.block_timestamp)
+            .block_index(self.block_height)
+            .storage_usage(deployment.storage_usage)
+            .prepaid_gas(PREPAID_GAS)
+            .build();
+        set_blockchain(context, promise_results, deployment.storage.clone());
+
+        let result = catch_panic(|| deployment.host.call(method, args));
+        self.logs.extend(with_mocked_blockchain(|b| b.logs()));
+        let value = result?;
+
+        deployment.storage = with_mocked_blockchain(|b| b.take_storage());
+        deployment.storage_usage = env::storage_usage();
+        account.balance = env::account_balance();
+
+        let receipts = with_mocked_blockchain(|b| b.created_receipts());
+        self.execute_receipts(signer, receiver, receipts);
+        Ok(value)
+    }
+
+    fn execute_receipts(&mut self, signer: &AccountId, sender: &AccountId, receipts: Vec<Receipt>) {
+        let mut results = Vec::new();
+
+        for receipt in receipts {
+            let is_callback = &receipt.receiver_id == sender;
+            let mut promise_results = if is_callback { mem::take(&mut results) } else { Vec::new() };
+            let mut outcome = PromiseResult::Successful(Vec::new());
+
+            for action in receipt.actions {
+                outcome = match action {
+                    VmAction::Transfer { deposit } => {
+                        self.transfer(sender, &receipt.receiver_id, deposit)
+                    },
+                    VmAction::FunctionCall { function_name, args, deposit, .. } => {
+                        let args = serde_json::from_slice(&args).unwrap_or(Value::Null);
+                        let result = self.execute(
+                            signer,
+                            sender,
+                            &receipt.receiver_id,
+                            &function_name,
+                            &args,
+                            deposit,
+                            mem::take(&mut promise_results),
+                        );
+
+                        match result {
+                            Ok(value) => PromiseResult::Successful(serde_json::to_vec(&value).unwrap()),
+                            Err(_) => {
+                                self.credit(sender, deposit);
+                                PromiseResult::Failed
+                            },
+                        }
+                    },
+                    _ => outcome,
+                };
+            }
+
+            if !is_callback {
+                results.push(outcome);
+            }
+        }
+    }
+
+    /// Transfers to accounts that don't exist fail and are refunded to the sender.
+    fn transfer(&mut self, sender: &AccountId, receiver: &AccountId, amount: Balance) -> PromiseResult {
+        if self.accounts.contains_key(receiver) {
+            self.credit(receiver, amount);
+            PromiseResult::Successful(Vec::new())
+        } else {
+            self.credit(sender, amount);
+            PromiseResult::Failed
+        }
+    }
+}
+
+fn set_blockchain(
+    context: near_sdk::VMContext,
+    promise_results: Vec<PromiseResult>,
+    storage: HashMap<Vec<u8>, Vec<u8>>,
+) {
+    env::set_blockchain_interface(MockedBlockchain::new(
+        context,
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        promise_results,
+        storage,
+        Default::default(),
+        None,
+    ));
+}
+
+fn catch_panic<F: FnOnce() -> Result<Value, String>>(f: F) -> Result<Value, String> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => Err(payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| String::from("Contract panicked"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::ONE_NEAR;
+    use serde_json::json;
+
+    fn account(name: &str) -> AccountId {
+        name.parse().unwrap()
+    }
+
+    fn setup() -> MockChain {
+        let mut chain = MockChain::new();
+        chain.create_account(account("alice"), 100 * ONE_NEAR);
+        chain.create_account(account("bob"), 100 * ONE_NEAR);
+        chain.deploy(account("sokoban"), Box::new(SokobanHost), 10 * ONE_NEAR);
+        chain.deploy(account("token"), Box::new(TokenHost), 10 * ONE_NEAR);
+
+        let outcome = chain.call(&account("alice"), &account("sokoban"), "new", json!({}), 0);
+        assert!(outcome.result.is_ok());
+        chain
+    }
+
+    #[test]
+    fn test_contracts_keep_separate_storage() {
+        let mut chain = setup();
+
+        let outcome = chain.call(
+            &account("alice"),
+            &account("token"),
+            "new_default_meta",
+            json!({ "owner_id": "alice", "total_supply": "1000" }),
+            0,
+        );
+        assert!(outcome.result.is_ok());
+
+        let supply = chain.view(&account("token"), "ft_total_supply", json!({}));
+        assert_eq!(supply, Ok(json!("1000")));
+
+        let bounds = chain.view(&account("sokoban"), "storage_balance_bounds", json!({}));
+        assert!(bounds.is_ok());
+    }
+
+    #[test]
+    fn test_deposit_moves_balance() {
+        let mut chain = setup();
+
+        let outcome = chain.call(&account("alice"), &account("sokoban"), "storage_deposit", json!({}), ONE_NEAR);
+        assert!(outcome.result.is_ok());
+        assert_eq!(chain.balance(&account("alice")), Some(99 * ONE_NEAR));
+        assert_eq!(chain.balance(&account("sokoban")), Some(11 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_panic_rolls_back_and_refunds() {
+        let mut chain = setup();
+
+        let outcome = chain.call(
+            &account("alice"),
+            &account("sokoban"),
+            "create_board",
+            json!({ "field": "QmA=", "field_size": { "width": 3, "height": 1 } }),
+            ONE_NEAR,
+        );
+
+        assert!(outcome.result.unwrap_err().contains("Account is not registered"));
+        assert_eq!(chain.balance(&account("alice")), Some(100 * ONE_NEAR));
+        assert_eq!(chain.view(&account("sokoban"), "get_board", json!({ "index": 0 })), Ok(Value::Null));
+    }
+
+    #[test]
+    fn test_withdraw_transfers_to_account() {
+        let mut chain = setup();
+
+        chain.call(&account("bob"), &account("sokoban"), "storage_deposit", json!({}), ONE_NEAR);
+        let outcome = chain.call(&account("bob"), &account("sokoban"), "storage_unregister", json!({}), 1);
+
+        assert_eq!(outcome.result, Ok(json!(true)));
+        assert_eq!(chain.balance(&account("bob")), Some(100 * ONE_NEAR - 1));
+    }
+}
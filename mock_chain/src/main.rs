@@ -0,0 +1,33 @@
+//! Runs scenario files against the mock chain: `run-scenario <file>...`
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+use mock_chain::scenario::Scenario;
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: run-scenario <scenario.json|scenario.yaml>...");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for path in paths {
+        match Scenario::load(Path::new(&path)).and_then(|scenario| scenario.run()) {
+            Ok(chain) => {
+                println!("ok   {}", path);
+                chain.logs().iter().for_each(|log| println!("     {}", log));
+            },
+            Err(error) => {
+                println!("FAIL {}: {}", path, error);
+                failed = true;
+            },
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::Path;
+
+use near_sdk::json_types::U128;
+use near_sdk::AccountId;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::*;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractKind {
+    Sokoban,
+    Token,
+}
+
+#[derive(Deserialize)]
+pub struct AccountSpec {
+    pub account_id: AccountId,
+    pub balance: U128,
+}
+
+#[derive(Deserialize)]
+pub struct ContractSpec {
+    pub account_id: AccountId,
+    pub kind: ContractKind,
+    pub balance: U128,
+}
+
+#[derive(Deserialize)]
+pub struct CallStep {
+    pub signer: AccountId,
+    pub contract: AccountId,
+    pub method: String,
+    #[serde(default)]
+    pub args: Value,
+    pub deposit: Option<U128>,
+    /// Value the call must return.
+    pub expect: Option<Value>,
+    /// Substring of the panic message the call must fail with.
+    pub expect_panic: Option<String>,
+    /// Substring that one of the logs of the call must contain.
+    pub expect_log: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Call(CallStep),
+    AdvanceTime { seconds: u64 },
+    ExpectBalance { account_id: AccountId, balance: U128 },
+}
+
+/// A list of calls and expectations run against a fresh `MockChain`.
+#[derive(Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub accounts: Vec<AccountSpec>,
+    #[serde(default)]
+    pub contracts: Vec<ContractSpec>,
+    pub steps: Vec<Step>,
+}
+
+impl Scenario {
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, String> {
+        serde_yaml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Reads a `.json`, `.yaml` or `.yml` scenario file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml") | Some("yml") => Self::from_yaml(&text),
+            _ => Self::from_json(&text),
+        }
+    }
+
+    /// Runs every step and returns the chain, or a description of the first
+    /// step that didn't meet its expectations.
+    pub fn run(&self) -> Result<MockChain, String> {
+        let mut chain = MockChain::new();
+
+        for account in &self.accounts {
+            chain.create_account(account.account_id.clone(), account.balance.0);
+        }
+        for contract in &self.contracts {
+            let host: Box<dyn HostedContract> = match contract.kind {
+                ContractKind::Sokoban => Box::new(SokobanHost),
+                ContractKind::Token => Box::new(TokenHost),
+            };
+            chain.deploy(contract.account_id.clone(), host, contract.balance.0);
+        }
+
+        for (index, step) in self.steps.iter().enumerate() {
+            run_step(&mut chain, step).map_err(|error| format!("Step {}: {}", index, error))?;
+        }
+
+        Ok(chain)
+    }
+}
+
+fn run_step(chain: &mut MockChain, step: &Step) -> Result<(), String> {
+    match step {
+        Step::AdvanceTime { seconds } => {
+            chain.advance_time(seconds * 10u64.pow(9));
+            Ok(())
+        },
+        Step::ExpectBalance { account_id, balance } => {
+            let actual = chain.balance(account_id).ok_or_else(|| format!("No account {}", account_id))?;
+            if actual != balance.0 {
+                return Err(format!("Balance of {} is {}, expected {}", account_id, actual, balance.0));
+            }
+            Ok(())
+        },
+        Step::Call(call) => {
+            let deposit = call.deposit.map_or(0, |deposit| deposit.0);
+            let outcome = chain.call(&call.signer, &call.contract, &call.method, call.args.clone(), deposit);
+
+            match (&outcome.result, &call.expect_panic) {
+                (Err(error), None) => return Err(format!("{} failed: {}", call.method, error)),
+                (Ok(_), Some(expected)) => {
+                    return Err(format!("{} succeeded, expected panic \"{}\"", call.method, expected))
+                },
+                (Err(error), Some(expected)) if !error.contains(expected.as_str()) => {
+                    return Err(format!("{} failed with \"{}\", expected \"{}\"", call.method, error, expected))
+                },
+                _ => (),
+            }
+
+            if let (Ok(result), Some(expected)) = (&outcome.result, &call.expect) {
+                if result != expected {
+                    return Err(format!("{} returned {}, expected {}", call.method, result, expected));
+                }
+            }
+
+            if let Some(expected) = &call.expect_log {
+                if !outcome.logs.iter().any(|log| log.contains(expected.as_str())) {
+                    return Err(format!("{} didn't log \"{}\"", call.method, expected));
+                }
+            }
+
+            Ok(())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wager_timeout_scenario() {
+        let scenario = Scenario::from_json(include_str!("../scenarios/wager_timeout.json")).unwrap();
+        scenario.run().unwrap();
+    }
+
+    #[test]
+    fn test_wager_win_scenario() {
+        let scenario = Scenario::from_yaml(include_str!("../scenarios/wager_win.yaml")).unwrap();
+        scenario.run().unwrap();
+    }
+
+    #[test]
+    fn test_failed_expectation_names_the_step() {
+        let scenario = Scenario::from_json(r#"{
+            "accounts": [{ "account_id": "alice", "balance": "10" }],
+            "steps": [
+                { "advance_time": { "seconds": 1 } },
+                { "expect_balance": { "account_id": "alice", "balance": "11" } }
+            ]
+        }"#).unwrap();
+
+        assert_eq!(scenario.run().err(), Some(String::from("Step 1: Balance of alice is 10, expected 11")));
+    }
+}
@@ -0,0 +1,314 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, require, AccountId, Balance, Promise, PromiseResult, Timestamp};
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub enum EscrowStatus {
+    AwaitingPlayer,
+    Running,
+    Settled,
+}
+
+/// Ledger entry of a wagered game: the sponsor bets that the player won't
+/// solve the board, the player matches the bid and wins the pot by solving it.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Escrow {
+    pub sponsor: AccountId,
+    pub player: AccountId,
+    pub sponsor_deposit: U128,
+    pub player_deposit: U128,
+    pub released: U128,
+    pub deadline: Timestamp,
+    pub status: EscrowStatus,
+}
+
+impl Escrow {
+    pub fn balance(&self) -> Balance {
+        self.sponsor_deposit.0 + self.player_deposit.0 - self.released.0
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn create_wagered_game(&mut self, index: BoardIndex, player: AccountId) -> GameIndex {
+        let sponsor = env::predecessor_account_id();
+        require!(env::attached_deposit() == BID, "Attached deposit must be equal to the bid");
+        require!(sponsor != player, "Can't wager against yourself");
+
+        let initial_storage_usage = env::storage_usage();
+        let game_index = self.internal_create_single_game(index, player.clone(), None);
+        let escrow = Escrow {
+            sponsor,
+            player,
+            sponsor_deposit: U128(BID),
+            player_deposit: U128(0),
+            released: U128(0),
+            deadline: env::block_timestamp() + WAGER_ACCEPT_TIMEOUT,
+            status: EscrowStatus::AwaitingPlayer,
+        };
+
+        self.escrows.insert(&game_index, &escrow);
+        self.internal_charge_game_storage(game_index, initial_storage_usage);
+        game_index
+    }
+
+    #[payable]
+    pub fn accept_wager(&mut self, index: GameIndex) {
+        let mut escrow = self.escrows.get(&index).expect("Game isn't wagered");
+        require!(escrow.status == EscrowStatus::AwaitingPlayer, "Wager has already been accepted");
+        require!(env::predecessor_account_id() == escrow.player, "Incorrect predecessor account");
+        require!(env::attached_deposit() == BID, "Attached deposit must be equal to the bid");
+        require!(env::block_timestamp() <= escrow.deadline, "Wager has expired");
+
+        escrow.player_deposit = U128(BID);
+        escrow.status = EscrowStatus::Running;
+        escrow.deadline = env::block_timestamp() + WAGER_MOVE_TIMEOUT;
+        self.escrows.insert(&index, &escrow);
+
+        let mut game = self.internal_get_game(index).expect("Game doesn't exist");
+        game.game_status = GameStatus::Running;
+        game.updated_at = env::block_timestamp();
+        game.version += 1;
+
+        SokobanEvent::GameStarted(GameStarted { game_index: index, player: &game.player }).emit();
+        self.games.insert(&index, &game.into());
+    }
+
+    /// Settles a wagered game whose deadline has passed. Can be called by anyone:
+    /// a wager that was never accepted is refunded to both sides, an idle player
+    /// forfeits the pot to the sponsor.
+    pub fn claim_timeout(&mut self, index: GameIndex) {
+        let escrow = self.escrows.get(&index).expect("Game isn't wagered");
+        require!(escrow.status != EscrowStatus::Settled, "Wager is already settled");
+        require!(env::block_timestamp() > escrow.deadline, "Deadline hasn't passed yet");
+
+        match escrow.status {
+            EscrowStatus::AwaitingPlayer => self.internal_refund(index, escrow),
+            EscrowStatus::Running => {
+                let sponsor = escrow.sponsor.clone();
+                self.internal_settle(index, escrow, sponsor);
+            },
+            EscrowStatus::Settled => (),
+        }
+    }
+
+    pub fn get_escrow(&self, index: GameIndex) -> Option<Escrow> {
+        self.escrows.get(&index)
+    }
+
+    pub fn get_unclaimed(&self, account_id: AccountId) -> U128 {
+        U128(self.unclaimed.get(&account_id).unwrap_or(0))
+    }
+
+    /// Retries the payouts that failed for the predecessor.
+    pub fn withdraw_unclaimed(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+        let amount = self.unclaimed.remove(&account_id).unwrap_or(0);
+        require!(amount > 0, "Nothing to withdraw");
+
+        self.internal_transfer(account_id, amount)
+    }
+
+    #[private]
+    pub fn on_escrow_transfer(&mut self, account_id: AccountId, amount: U128) {
+        require!(env::promise_results_count() == 1, "Expected exactly one promise result");
+
+        match env::promise_result(0) {
+            PromiseResult::Successful(_) => {
+                env::log_str(&format!("Transferred {} to {}", amount.0, account_id));
+            },
+            _ => {
+                let unclaimed = self.unclaimed.get(&account_id).unwrap_or(0);
+                self.unclaimed.insert(&account_id, &(unclaimed + amount.0));
+                env::log_str(&format!("Transfer of {} to {} failed, rolled back", amount.0, account_id));
+            },
+        }
+    }
+}
+
+impl Contract {
+    /// Checks the move deadline of a wagered game and extends it for the next move.
+    pub(crate) fn internal_escrow_before_step(&mut self, index: GameIndex) {
+        if let Some(mut escrow) = self.escrows.get(&index) {
+            require!(escrow.status == EscrowStatus::Running, "Wager is not running");
+            require!(env::block_timestamp() <= escrow.deadline, "Move deadline has passed");
+
+            escrow.deadline = env::block_timestamp() + WAGER_MOVE_TIMEOUT;
+            self.escrows.insert(&index, &escrow);
+        }
+    }
+
+    pub(crate) fn internal_escrow_on_finish(&mut self, index: GameIndex) {
+        if let Some(escrow) = self.escrows.get(&index) {
+            let player = escrow.player.clone();
+            self.internal_settle(index, escrow, player);
+        }
+    }
+
+    fn internal_settle(&mut self, index: GameIndex, mut escrow: Escrow, winner: AccountId) {
+        let pot = escrow.balance();
+        let fee = std::cmp::min(FEE, pot);
+
+        escrow.released = U128(escrow.released.0 + pot);
+        escrow.status = EscrowStatus::Settled;
+        self.escrows.insert(&index, &escrow);
+        self.fees_collected += fee;
+
+        if pot > fee {
+            self.internal_transfer(winner, pot - fee);
+        }
+    }
+
+    fn internal_refund(&mut self, index: GameIndex, mut escrow: Escrow) {
+        let refunds = [
+            (escrow.sponsor.clone(), escrow.sponsor_deposit.0),
+            (escrow.player.clone(), escrow.player_deposit.0),
+        ];
+
+        escrow.released = U128(escrow.released.0 + escrow.balance());
+        escrow.status = EscrowStatus::Settled;
+        self.escrows.insert(&index, &escrow);
+
+        for (account_id, amount) in refunds {
+            if amount > 0 {
+                self.internal_transfer(account_id, amount);
+            }
+        }
+    }
+
+    fn internal_transfer(&mut self, account_id: AccountId, amount: Balance) -> Promise {
+        Promise::new(account_id.clone())
+            .transfer(amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_ESCROW_CALLBACK)
+                    .on_escrow_transfer(account_id, U128(amount))
+            )
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig, ONE_NEAR};
+    use near_contract_standards::storage_management::StorageManagement;
+
+    fn get_context(account: AccountId, deposit: Balance, timestamp: Timestamp) -> near_sdk::VMContext {
+        VMContextBuilder::new()
+            .current_account_id(accounts(5))
+            .predecessor_account_id(account)
+            .attached_deposit(deposit)
+            .block_timestamp(timestamp)
+            .storage_usage(env::storage_usage())
+            .build()
+    }
+
+    fn setup_wager() -> (Contract, GameIndex) {
+        testing_env!(get_context(accounts(1), ONE_NEAR, 0));
+        let mut contract = Contract::new();
+        contract.storage_deposit(None, None);
+
+        testing_env!(get_context(accounts(1), BID, 0));
+
+        let mut board = Board::new(Size { width: 3, height: 1 });
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 6).unwrap();
+        let index = contract.create_board(board.field.into(), Some(Size { width: 3, height: 1 }), None);
+
+        let game_index = contract.create_wagered_game(index, accounts(0));
+        (contract, game_index)
+    }
+
+    #[test]
+    fn test_accept_and_win_wager() {
+        let (mut contract, game_index) = setup_wager();
+
+        testing_env!(get_context(accounts(0), BID, 10));
+        contract.accept_wager(game_index);
+        assert_eq!(contract.get_escrow(game_index).unwrap().status, EscrowStatus::Running);
+
+        testing_env!(get_context(accounts(0), 0, 20));
+        contract.step(game_index, Direction::Forward);
+
+        let escrow = contract.get_escrow(game_index).unwrap();
+        assert_eq!(escrow.status, EscrowStatus::Settled);
+        assert_eq!(escrow.balance(), 0);
+        assert_eq!(contract.fees_collected, FEE);
+    }
+
+    #[test]
+    fn test_claim_never_accepted_wager_refunds() {
+        let (mut contract, game_index) = setup_wager();
+
+        testing_env!(get_context(accounts(2), 0, WAGER_ACCEPT_TIMEOUT + 1));
+        contract.claim_timeout(game_index);
+
+        let escrow = contract.get_escrow(game_index).unwrap();
+        assert_eq!(escrow.status, EscrowStatus::Settled);
+        assert_eq!(escrow.released.0, BID);
+        assert_eq!(contract.fees_collected, 0);
+    }
+
+    #[test]
+    fn test_claim_idle_player_forfeits() {
+        let (mut contract, game_index) = setup_wager();
+
+        testing_env!(get_context(accounts(0), BID, 10));
+        contract.accept_wager(game_index);
+
+        testing_env!(get_context(accounts(2), 0, 11 + WAGER_MOVE_TIMEOUT));
+        contract.claim_timeout(game_index);
+
+        let escrow = contract.get_escrow(game_index).unwrap();
+        assert_eq!(escrow.status, EscrowStatus::Settled);
+        assert_eq!(escrow.released.0, 2 * BID);
+        assert_eq!(contract.fees_collected, FEE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Deadline hasn't passed yet")]
+    fn test_claim_before_deadline() {
+        let (mut contract, game_index) = setup_wager();
+
+        testing_env!(get_context(accounts(2), 0, 10));
+        contract.claim_timeout(game_index);
+    }
+
+    #[test]
+    #[should_panic(expected = "Move deadline has passed")]
+    fn test_step_after_deadline() {
+        let (mut contract, game_index) = setup_wager();
+
+        testing_env!(get_context(accounts(0), BID, 10));
+        contract.accept_wager(game_index);
+
+        testing_env!(get_context(accounts(0), 0, 11 + WAGER_MOVE_TIMEOUT));
+        contract.step(game_index, Direction::Forward);
+    }
+
+    #[test]
+    fn test_failed_transfer_is_rolled_back() {
+        let (mut contract, _) = setup_wager();
+
+        testing_env!(
+            get_context(accounts(5), 0, 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_escrow_transfer(accounts(1), U128(BID));
+
+        assert_eq!(contract.get_unclaimed(accounts(1)).0, BID);
+        assert!(get_logs()[0].contains("rolled back"));
+    }
+}
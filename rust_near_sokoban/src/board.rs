@@ -1,508 +1,184 @@
-use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::json_types::Base64VecU8;
-use near_sdk::{env, require};
+use near_sdk::env;
+
+pub use sokoban_core::board::{Board, BoardState, Ruleset, Topology, WinCondition, MAX_COLORS};
+pub use sokoban_core::cell::{Cell, CellEncoding};
+pub use sokoban_core::error::SokobanError;
 
 use crate::auxiliary::*;
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+/// JSON representation of a `Board`, with the packed field encoded as base64.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 #[serde(crate = "near_sdk::serde")]
-pub struct Board {
+pub struct BoardView {
     pub field: Base64VecU8,
     pub is_valid: bool,
-    pub sokoban_position: Option<Point>, 
-    pub size: Size, 
+    pub sokoban_position: Option<Point>,
+    pub size: Size,
     pub field_len: usize,
+    /// Missing in views of boards created before the extended encoding.
+    #[serde(default)]
+    pub encoding: CellEncoding,
+    #[serde(default)]
+    pub ruleset: Ruleset,
+    #[serde(default)]
+    pub teleporters: Vec<(Point, Point)>,
+    #[serde(default)]
+    pub box_colors: Vec<(Point, u8)>,
+    #[serde(default)]
+    pub goal_colors: Vec<(Point, u8)>,
+    #[serde(default)]
+    pub one_way: Vec<(Point, Direction)>,
+    #[serde(default)]
+    pub gates: Vec<(Point, Point)>,
+    #[serde(default = "default_agents")]
+    pub agents: u8,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default)]
+    pub win_condition: WinCondition,
 }
 
-impl Board {
-    pub fn new(size: Size) -> Self {
-        let mut field_len = size.width * size.height;
-        if field_len % 2 == 1 {
-            field_len += 1;
-        }
+fn default_agents() -> u8 {
+    1
+}
 
-        field_len /= 2;
+/// Optional settings of `create_board`. Missing fields take their defaults.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(default)]
+pub struct BoardOptions {
+    pub encoding: CellEncoding,
+    pub ruleset: Ruleset,
+    /// Pairs of linked teleporter cells, see `Board::teleporters`.
+    pub teleporters: Vec<(Point, Point)>,
+    /// Colours of boxes and goals that aren't plain, see `MAX_COLORS`.
+    pub box_colors: Vec<(Point, u8)>,
+    pub goal_colors: Vec<(Point, u8)>,
+    /// Directions of one-way cells, see `Board::one_way`.
+    pub one_way: Vec<(Point, Direction)>,
+    /// Links from pressure plates to gates, see `Board::gates`.
+    pub gates: Vec<(Point, Point)>,
+    /// Number of sokobans on the board, see `Board::agents`.
+    pub agents: u8,
+    pub topology: Topology,
+    pub win_condition: WinCondition,
+}
 
+impl Default for BoardOptions {
+    fn default() -> Self {
         Self {
-            field: Base64VecU8::from(vec![0u8; field_len]),
-            is_valid: false,
-            sokoban_position: None,
-            field_len,
-            size,
-        }
-    }
-
-    pub fn from(field: Base64VecU8, size: Size) -> Self {
-        let mut field_len = size.width * size.height;
-        if field_len % 2 == 1 {
-            field_len += 1;
-        }
-
-        field_len /= 2;
-
-        require!(field.0.len() == field_len, "Passed field_len and passed vector length don't match");
-        let board = Self {
-            field, 
-            is_valid: false,
-            sokoban_position: Option::None,
-            field_len,
-            size,
-        };
-
-        board.validate_board()
-    }
-
-    pub fn get_state_at_cell(&self, cord: Point) -> Option<u8> {
-        let x = cord.x;
-        let y = cord.y;
-
-        if x >= self.size.width || y >= self.size.height {
-            return Option::None 
-        }
-
-        let cell_index = y * self.size.width + x;
-        let in_vector_index = cell_index / 2;
-        let in_u8_index = cell_index % 2;
-
-        if in_u8_index == 1 {
-            Some(self.field.0[in_vector_index] & 0x0F)
-        } else {
-            Some(self.field.0[in_vector_index] >> 4)
+            encoding: CellEncoding::default(),
+            ruleset: Ruleset::default(),
+            teleporters: Vec::new(),
+            box_colors: Vec::new(),
+            goal_colors: Vec::new(),
+            one_way: Vec::new(),
+            gates: Vec::new(),
+            agents: default_agents(),
+            topology: Topology::default(),
+            win_condition: WinCondition::default(),
         }
     }
+}
 
-    pub fn set_state_at_cell(&mut self, cord: Point, state: u8) {
-        let x = cord.x;
-        let y = cord.y;
-
-        require!(state <= 6, "There is no such available state");
-        require!(x < self.size.width, "Attempt of setting a value beyond the field");
-        require!(y < self.size.height, "Attempt of setting a value beyond the field");
-
-        let cell_index = y * self.size.width + x;
-        let in_vector_index = cell_index / 2;
-        let in_u8_index = cell_index % 2;
-
-        let mut value: u8 = self.field.0[in_vector_index];
-
-        if in_u8_index == 1 {
-            value &= 0b11110000;
-            value |= state;
-        } else {
-            value = (state << 4) | (value & 15);
-        }
-
-        self.field.0[in_vector_index] = value;
-    }
-
-    pub fn validate_board(&self) -> Self {
-        let mut board : Board = self.clone();
-
-        let mut sokoban_counter = 0;
-        let mut box_counter = 0; 
-        let mut dest_counter = 0;
-
-        let mut sokoban_position: Point = Point { x: 0, y: 0 };
-
-        for x in 0..self.size.width {
-            for y in 0..self.size.height {
-                match board.get_state_at_cell(Point { x, y }).unwrap() {
-                    2 => box_counter += 1,
-                    4 => { 
-                        sokoban_counter += 1;
-                        sokoban_position.x = x;
-                        sokoban_position.y = y;
-                    }
-                    5 => {
-                        sokoban_counter += 1;
-                        dest_counter += 1;
-                        sokoban_position.x = x;
-                        sokoban_position.y = y;
-                    },
-                    6 => dest_counter +=1,
-                    _ => ()
-                };
-            }
-        }
-
-        let is_valid = sokoban_counter == 1 && box_counter == dest_counter;
-        board.is_valid = is_valid;
-
-        if is_valid {
-            board.sokoban_position = Option::Some(sokoban_position);
-        }
-
-        board
-    }
-
-    pub fn check_if_finished(&self) -> bool {
-        if !self.is_valid {
-            return false;
-        }
-
-        let mut box_counter = 0; 
-
-        for x in 0..self.size.width {
-            for y in 0..self.size.height {
-                if self.get_state_at_cell(Point { x, y }).unwrap() == 2 {
-                    box_counter += 1;
-                }
-            }
-        }
-
-        return box_counter == 0;
-    }
-
-    pub fn make_step(&self, direction: Direction) -> Self {
-        let mut board : Board = self.clone();
-        let cur_cell = board.sokoban_position.expect("Invalid board");
-
-        let next_cell = cur_cell.get_point_in_direction(direction);
-
-        if next_cell == None { return board; }
-        let next_cell = next_cell.unwrap();
-
-        let after_next_cell = next_cell.get_point_in_direction(direction);
-
-        if let Some(state_at_next_cell) = board.get_state_at_cell(next_cell) {
-            match state_at_next_cell {
-                1 => {
-                    board.set_state_at_cell(next_cell, 4);
-                    board.sokoban_position = Some(next_cell);
-                    board.set_state_at_cell(cur_cell, 1);
-                },
-                2 => {
-                    if after_next_cell == None { return board; } 
-                    let after_next_cell = after_next_cell.unwrap();
-
-                    let state = board.get_state_at_cell(after_next_cell);
-
-                    if let Some(state) = state {
-                        if state == 1 {
-                            board.set_state_at_cell(next_cell, 4);
-                            board.sokoban_position = Some(next_cell);
-                            board.set_state_at_cell(cur_cell, 1);
-                            board.set_state_at_cell(after_next_cell, 2)
-                        } else if state == 6 {
-                            board.set_state_at_cell(next_cell, 4);
-                            board.sokoban_position = Some(next_cell);
-                            board.set_state_at_cell(cur_cell, 1);
-                            board.set_state_at_cell(after_next_cell, 3)
-                        }
-                    } 
-                },
-                3 => {
-                    if after_next_cell == None { return board; } 
-                    let after_next_cell = after_next_cell.unwrap();
-
-                    let state = board.get_state_at_cell(after_next_cell);
-
-                    if let Some(state) = state {
-                        if state == 1 {
-                            board.set_state_at_cell(next_cell, 5);
-                            board.sokoban_position = Some(next_cell);
-                            board.set_state_at_cell(cur_cell, 1);
-                            board.set_state_at_cell(after_next_cell, 2)
-                        } else if state == 6 {
-                            board.set_state_at_cell(next_cell, 5);
-                            board.sokoban_position = Some(next_cell);
-                            board.set_state_at_cell(cur_cell, 1);
-                            board.set_state_at_cell(after_next_cell, 3)
-                        }
-                    }
-                }
-                6 => {
-                    board.set_state_at_cell(next_cell, 5);
-                    board.sokoban_position = Some(next_cell);
-                    board.set_state_at_cell(cur_cell, 1);
-                },
-                _ => ()
-            };
-        } 
-
-        board 
-    }
-
-    pub fn clone(&self) -> Self {
+impl From<Board> for BoardView {
+    fn from(board: Board) -> Self {
         Self {
-            field: self.field.clone(),
-            ..*self
-        }
-    }
-
-    pub fn state_as_symbol(state: u8) -> char {
-        match state {
-            0 => '*',
-            1 => '.',
-            2 => 'c',
-            3 => 'C',
-            4 => 's',
-            5 => 'S',
-            6 => 'X',
-            _ => panic!("Invalid map")
+            field: board.field.into(),
+            is_valid: board.is_valid,
+            sokoban_position: board.sokoban_position,
+            size: board.size,
+            field_len: board.field_len,
+            encoding: board.encoding,
+            ruleset: board.ruleset,
+            teleporters: board.teleporters,
+            box_colors: board.box_colors,
+            goal_colors: board.goal_colors,
+            one_way: board.one_way,
+            gates: board.gates,
+            agents: board.agents,
+            topology: board.topology,
+            win_condition: board.win_condition,
         }
     }
+}
 
-    pub fn get_board_as_strings(&self) -> Vec<String> {
-        let mut vector = Vec::new();
-
-        for i in 0..self.size.height {
-            let mut result = String::from("");
-            for j in 0..self.size.width {
-                let unwraped_cell = self.get_state_at_cell(Point { x: j, y: i }).unwrap();
-                let symbol = Self::state_as_symbol(unwraped_cell);
-                result.push(symbol);
-            }
-            vector.push(result);
-        }
+impl TryFrom<BoardView> for Board {
+    type Error = SokobanError;
 
-        vector
+    fn try_from(view: BoardView) -> Result<Self, Self::Error> {
+        Ok(Board::from_encoded(view.field.into(), view.size, view.encoding)?
+            .with_ruleset(view.ruleset)
+            .with_teleporters(view.teleporters)
+            .with_colors(view.box_colors, view.goal_colors)
+            .with_one_way(view.one_way)
+            .with_gates(view.gates)
+            .with_agents(view.agents)
+            .with_topology(view.topology)
+            .with_win_condition(view.win_condition))
     }
+}
 
-    pub fn debug_logs(&self) {
-        self.get_board_as_strings()
-            .into_iter()
-            .for_each(|s| env::log_str(&s));
+/// Turns engine errors into panics at the contract boundary. The panic hook
+/// installed by `near_bindgen` reports the message like `env::panic_str` does.
+pub trait OrPanic<T> {
+    fn or_panic(self) -> T;
+}
+
+impl<T> OrPanic<T> for Result<T, SokobanError> {
+    fn or_panic(self) -> T {
+        self.unwrap_or_else(|error| panic!("{}", error))
     }
 }
 
+/// Logs the rows of `board`. Only called when the `debug-logs` feature is enabled.
+pub fn debug_logs(board: &Board) {
+    board.get_board_as_strings()
+        .or_panic()
+        .into_iter()
+        .for_each(|s| env::log_str(&s));
+}
 
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    fn get_board_as_string(board: &Board) -> String {
-        board.get_board_as_strings().join("\n")
-    }
-
-    #[allow(dead_code)]
-    fn debug_board(board: &Board) {
-        print!("{}", get_board_as_string(&board));
-    }
-
-    #[test]
-    fn test_get_set_state_single() {
-        let mut board = Board::new(Size { width: 1, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-
-        let expected_board = String::from("s");
-        assert_eq!(expected_board, get_board_as_string(&board));
-    }
-
-    #[test]
-    fn test_get_set_state_one_crate() {
-        let mut board = Board::new(Size { width: 4, height: 2 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 1);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 2, y: 0 }, 2);
-        board.set_state_at_cell(Point { x: 3, y: 0 }, 6);
-        board.set_state_at_cell(Point { x: 1, y: 1 }, 1);
-        board.set_state_at_cell(Point { x: 3, y: 1 }, 5);
-
-        let expected_board = String::from(".scX\n*.*S");
-        assert_eq!(expected_board, get_board_as_string(&board));
-    }
-
-    #[test]
-    fn test_get_set_state_all_available() {
-        let mut board = Board::new(Size { width: 7, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 0);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 1);
-        board.set_state_at_cell(Point { x: 2, y: 0 }, 2);
-        board.set_state_at_cell(Point { x: 3, y: 0 }, 3);
-        board.set_state_at_cell(Point { x: 4, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 5, y: 0 }, 5);
-        board.set_state_at_cell(Point { x: 6, y: 0 }, 6);
-
-        let expected_board = String::from("*.cCsSX");
-        assert_eq!(expected_board, get_board_as_string(&board));
-    }
-
-    #[test]
-    #[should_panic]
-    fn test_set_unavailable_state() {
-        let mut board = Board::new(Size { width: 1, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 7);
-    }
-
-    #[test]
-    fn test_get_state_at_unavailable_position() {
-        let board = Board::new(Size { width: 2, height: 2 });
-
-        assert_eq!(Option::None, board.get_state_at_cell(Point { x: 3, y: 0}));
-        assert_eq!(Option::None, board.get_state_at_cell(Point { x: 0, y: 3}));
-        assert_eq!(Option::None, board.get_state_at_cell(Point { x: 3, y: 3}));
-    }
-
-    #[test]
-    fn test_try_validate_invalid_board_two_sokobans() {
-        let mut board = Board::new(Size { width: 9, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 5);
-
-        board = board.validate_board();
-        assert_eq!(false, board.is_valid);
-    }
-
-    #[test]
-    fn test_try_validate_invalid_board_boxes_dests_not_eq() {
-        let mut board = Board::new(Size { width: 9, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 2);
-
-        board = board.validate_board();
-        assert_eq!(false, board.is_valid);
-    }
-
-    #[test]
-    fn test_try_validate_valid_board() {
-        let mut board = Board::new(Size { width: 9, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 2);
-        board.set_state_at_cell(Point { x: 2, y: 0 }, 6);
-
-        board = board.validate_board();
-        assert_eq!(true, board.is_valid);
-    }
-
-    #[test]
-    fn test_make_one_step_move_box_on_destination() {
-        let mut board = Board::new(Size { width: 4, height: 2 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 1);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 2, y: 0 }, 2);
-        board.set_state_at_cell(Point { x: 3, y: 0 }, 6);
-        board.set_state_at_cell(Point { x: 1, y: 1 }, 1);
-
-        board = board.validate_board();
-        board = board.make_step(Direction::Forward);
-
-        let expected_board = String::from("..sC\n*.**");
-        assert_eq!(expected_board, get_board_as_string(&board));
-    }
-
-    #[test]
-    fn test_make_one_step_move_box_from_destionation() {
-        let mut board = Board::new(Size { width: 4, height: 2 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 1);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 2, y: 0 }, 3);
-        board.set_state_at_cell(Point { x: 3, y: 0 }, 1);
-        board.set_state_at_cell(Point { x: 1, y: 1 }, 1);
-
-        board = board.validate_board();
-        board = board.make_step(Direction::Forward);
-
-        let expected_board = String::from("..Sc\n*.**");
-        assert_eq!(expected_board, get_board_as_string(&board));
-    }
-
-    #[test]
-    fn test_make_one_step_move_sokoban() {
-        let mut board = Board::new(Size { width: 2, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 1);
-
-        board = board.validate_board();
-        board = board.make_step(Direction::Forward);
-
-        let expected_board = String::from(".s");
-        assert_eq!(expected_board, get_board_as_string(&board));
-    }
-
-    #[test]
-    fn test_make_one_step_move_sokoban_on_destination() {
-        let mut board = Board::new(Size { width: 3, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 6);
-        board.set_state_at_cell(Point { x: 2, y: 0 }, 2);
-
-        board = board.validate_board();
-        board = board.make_step(Direction::Forward);
-
-        let expected_board = String::from(".Sc");
-        assert_eq!(expected_board, get_board_as_string(&board));
+    use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+    use near_sdk::serde_json;
+
+    fn get_board(encoding: CellEncoding) -> Board {
+        let mut board = Board::new_with_encoding(Size { width: 3, height: 1 }, encoding);
+        board.set_cell(Point { x: 0, y: 0 }, Cell::Sokoban).unwrap();
+        board.set_cell(Point { x: 1, y: 0 }, Cell::Box).unwrap();
+        board.set_cell(Point { x: 2, y: 0 }, Cell::Goal).unwrap();
+        board.validate_board()
     }
 
     #[test]
-    fn test_make_one_step_move_sokoban_out_of_field_left() {
-        let mut board = Board::new(Size { width: 1, height: 1 });
+    fn test_board_encodings_round_trip() {
+        for encoding in [CellEncoding::Classic, CellEncoding::Extended] {
+            let board = get_board(encoding);
 
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-       
-        board = board.validate_board();
-        board.make_step(Direction::Backward);
-
-        let expected_board = String::from("s");
-        assert_eq!(expected_board, get_board_as_string(&board));
-    }
+            let bytes = board.try_to_vec().unwrap();
+            assert_eq!(Board::try_from_slice(&bytes).unwrap(), board);
 
-    #[test]
-    fn test_make_one_step_move_sokoban_out_of_field_right() {
-        let mut board = Board::new(Size { width: 1, height: 1 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-       
-        board = board.validate_board();
-        board = board.make_step(Direction::Forward);
+            let json = serde_json::to_string(&BoardView::from(board.clone())).unwrap();
+            let view: BoardView = serde_json::from_str(&json).unwrap();
+            assert_eq!(Board::try_from(view).unwrap(), board);
+        }
 
-        let expected_board = String::from("s");
-        assert_eq!(expected_board, get_board_as_string(&board));
+        assert_eq!(get_board(CellEncoding::Classic).field, vec![0x42, 0x60]);
+        assert_eq!(get_board(CellEncoding::Extended).field, vec![4, 2, 6]);
     }
 
     #[test]
-    fn test_run_simple_game() {
-        let mut board = Board::new(Size { width: 5, height: 4 });
-
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 1);
-        board.set_state_at_cell(Point { x: 2, y: 0 }, 2);
-        board.set_state_at_cell(Point { x: 3, y: 0 }, 6);
-        board.set_state_at_cell(Point { x: 0, y: 1 }, 1);
-        board.set_state_at_cell(Point { x: 1, y: 1 }, 2);
-        board.set_state_at_cell(Point { x: 2, y: 1 }, 1);
-        board.set_state_at_cell(Point { x: 3, y: 1 }, 1);
-        board.set_state_at_cell(Point { x: 0, y: 2 }, 1);
-        board.set_state_at_cell(Point { x: 1, y: 2 }, 6);
-        board.set_state_at_cell(Point { x: 2, y: 2 }, 1);
-        board.set_state_at_cell(Point { x: 3, y: 2 }, 3);
-
-        let d = Direction::Forward;
-        let s: String = near_sdk::serde_json::to_string(&d).unwrap();
-        println!("{}", s);
-
-        let mut board = board.validate_board();
-
-        let actions = [
-            Direction::Forward, 
-            Direction::Forward,
-            Direction::Backward,
-            Direction::Down
-        ];
-
-        let game_states = [
-            String::from(".scX*\n.c..*\n.X.C*\n*****"), 
-            String::from("..sC*\n.c..*\n.X.C*\n*****"), 
-            String::from(".s.C*\n.c..*\n.X.C*\n*****"),
-            String::from("...C*\n.s..*\n.C.C*\n*****")
-        ];
+    fn test_board_view_without_encoding_is_classic() {
+        let json = r#"{"field":"QmA=","is_valid":true,"sokoban_position":{"x":0,"y":0},"size":{"width":3,"height":1},"field_len":2}"#;
+        let view: BoardView = serde_json::from_str(json).unwrap();
 
-        for (index, action) in actions.iter().enumerate() {
-            board = board.make_step(*action);
-            assert_eq!(game_states[index], get_board_as_string(&board));
-        }   
+        assert_eq!(view.encoding, CellEncoding::Classic);
+        assert_eq!(Board::try_from(view).unwrap(), get_board(CellEncoding::Classic));
     }
 }
@@ -0,0 +1,162 @@
+//! NEP-297 events of the contract. Every event is logged as
+//! `EVENT_JSON:{"standard":"sokoban","version":"1.0.0","event":...,"data":...}`.
+
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+use near_sdk::{env, AccountId};
+
+use crate::auxiliary::*;
+use crate::moderation::{ModerationAction, ModerationReason};
+use crate::{BoardIndex, GameIndex};
+
+pub const EVENT_STANDARD: &str = "sokoban";
+pub const EVENT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+#[must_use = "don't forget to `.emit()` this event"]
+pub enum SokobanEvent<'a> {
+    BoardCreated(BoardCreated<'a>),
+    BoardValidated(BoardValidated),
+    GameCreated(GameCreated<'a>),
+    GameStarted(GameStarted<'a>),
+    Move(Move<'a>),
+    GameFinished(GameFinished<'a>),
+    BoardModerated(BoardModerated<'a>),
+    GameDeleted(GameDeleted<'a>),
+    BoardDeleted(BoardDeleted<'a>),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+struct NearEvent<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: SokobanEvent<'a>,
+}
+
+impl SokobanEvent<'_> {
+    pub fn to_json_event_string(self) -> String {
+        let event = NearEvent { standard: EVENT_STANDARD, version: EVENT_VERSION, event: self };
+        // Events cannot fail to serialize so fine to panic on error
+        format!("EVENT_JSON:{}", serde_json::to_string(&event).unwrap_or_else(|_| env::abort()))
+    }
+
+    pub fn emit(self) {
+        env::log_str(&self.to_json_event_string());
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BoardCreated<'a> {
+    pub board_index: BoardIndex,
+    pub author: &'a AccountId,
+    pub size: Size,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BoardValidated {
+    pub board_index: BoardIndex,
+    pub is_valid: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameCreated<'a> {
+    pub game_index: GameIndex,
+    pub board_index: BoardIndex,
+    pub player: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameStarted<'a> {
+    pub game_index: GameIndex,
+    pub player: &'a AccountId,
+}
+
+/// A step that changed the position. `pushed` is set when a box was moved.
+/// `player` is the account that moved, `agent` is only set on boards with
+/// several sokobans.
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Move<'a> {
+    pub game_index: GameIndex,
+    pub player: &'a AccountId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent: Option<u8>,
+    pub direction: Heading,
+    pub pushed: bool,
+    pub sokoban_position: Point,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameFinished<'a> {
+    pub game_index: GameIndex,
+    pub player: &'a AccountId,
+    pub moves: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BoardModerated<'a> {
+    pub board_index: BoardIndex,
+    pub moderator: &'a AccountId,
+    pub action: ModerationAction,
+    pub reason: ModerationReason,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GameDeleted<'a> {
+    pub game_index: GameIndex,
+    pub player: &'a AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BoardDeleted<'a> {
+    pub board_index: BoardIndex,
+    pub author: &'a AccountId,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+
+    #[test]
+    fn test_move_event_format() {
+        let player = accounts(0);
+        let event = SokobanEvent::Move(Move {
+            game_index: 3,
+            player: &player,
+            agent: None,
+            direction: Direction::Forward.into(),
+            pushed: true,
+            sokoban_position: Point { x: 1, y: 2 },
+        });
+
+        assert_eq!(
+            event.to_json_event_string(),
+            r#"EVENT_JSON:{"standard":"sokoban","version":"1.0.0","event":"move","data":{"game_index":3,"player":"alice","direction":"Forward","pushed":true,"sokoban_position":{"x":1,"y":2}}}"#
+        );
+    }
+
+    #[test]
+    fn test_board_validated_event_format() {
+        let event = SokobanEvent::BoardValidated(BoardValidated { board_index: 0, is_valid: false });
+
+        assert_eq!(
+            event.to_json_event_string(),
+            r#"EVENT_JSON:{"standard":"sokoban","version":"1.0.0","event":"board_validated","data":{"board_index":0,"is_valid":false}}"#
+        );
+    }
+}
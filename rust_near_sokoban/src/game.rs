@@ -1,9 +1,11 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, require, AccountId};
+use near_sdk::{env, require, AccountId, Timestamp};
 
 use crate::board::*;
 use crate::auxiliary::*;
+use crate::utils::POSITION_HISTORY_LEN;
+use crate::BoardIndex;
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -12,7 +14,7 @@ pub struct Player {
 	pub roketo_stream: String, // near sdk CryptoHash
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
 #[serde(crate = "near_sdk::serde")]
 pub enum GameStatus {
 	Unactive,
@@ -20,38 +22,298 @@ pub enum GameStatus {
 	Finished,
 }
 
+/// When the accounts of a cooperative game may move.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TurnOrder {
+	/// Either account may move at any time.
+	FreeForAll,
+	/// The accounts take turns, the player first. A call that moves ends the turn.
+	Alternating,
+}
+
+/// A second account sharing a game: the player moves agent 0, the partner every other agent.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Cooperation {
+	pub partner: AccountId,
+	pub turn_order: TurnOrder,
+	/// Whether the partner moves next under `TurnOrder::Alternating`.
+	pub partners_turn: bool,
+}
+
+/// What a single call to `make_step` did to the position.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct StepResult {
+	pub moved: bool,
+	/// Whether a box was moved, pulled ones included.
+	pub pushed: bool,
+}
+
+/// New state of a cell, in the encoding of `Board::field`.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CellChange {
+	pub point: Point,
+	pub state: u8,
+}
+
+/// What `step` and `steps` return instead of the whole game. A client that knows
+/// the game at `version - 1` can apply `changes` to its copy; any other version
+/// means it missed an update and should fetch the game again.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StepDelta {
+	pub version: u64,
+	pub changes: Vec<CellChange>,
+	pub moved: bool,
+	/// Whether any of the moves pushed a box.
+	pub pushed: bool,
+	/// Position of the sokoban that was moved.
+	pub sokoban_position: Point,
+	pub game_status: GameStatus,
+}
+
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub struct SingleplayerGame {
-	pub board: Board,
+	pub board_index: BoardIndex,
+	pub state: BoardState,
 	pub player: AccountId,
 	pub game_status: GameStatus, 
+	/// Zobrist hashes of the last `POSITION_HISTORY_LEN` positions, the current one last.
+	pub position_history: Vec<u64>,
+	/// How many moves led to a position still in `position_history`.
+	pub repeated_positions: u32,
+	/// Number of moves that changed the position.
+	pub move_count: u64,
+	/// When the game was created, started or last moved.
+	pub updated_at: Timestamp,
+	/// Increased by every call that changes the game.
+	pub version: u64,
+	/// Set when a partner controls some of the sokobans.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub cooperation: Option<Cooperation>,
 }
 
 impl SingleplayerGame {
-	pub fn from(board: Board, player: AccountId) -> Self {
+	pub fn from(board_index: BoardIndex, board: &Board, player: AccountId) -> Self {
+        let state = board.start_state().or_panic();
+
         Self {
-            board, 
+            board_index,
+            position_history: vec![state.hash],
+            state,
             player, 
             game_status: GameStatus::Unactive,
+            repeated_positions: 0,
+            move_count: 0,
+            updated_at: env::block_timestamp(),
+            version: 0,
+            cooperation: None,
         }
     }
 
-    pub fn make_step(&mut self, direction: Direction) {
+    /// Account that moves the sokoban of `agent`.
+    pub fn controller(&self, agent: u8) -> &AccountId {
+        match &self.cooperation {
+            Some(cooperation) if agent > 0 => &cooperation.partner,
+            _ => &self.player,
+        }
+    }
+
+    /// Whether `account` may move now. Only alternating games restrict it.
+    pub fn is_turn_of(&self, account: &AccountId) -> bool {
+        match &self.cooperation {
+            Some(Cooperation { partner, turn_order: TurnOrder::Alternating, partners_turn }) => {
+                (account == partner) == *partners_turn
+            },
+            _ => true,
+        }
+    }
+
+    /// Passes the turn to the other account of an alternating game.
+    pub fn end_turn(&mut self) {
+        if let Some(cooperation) = &mut self.cooperation {
+            cooperation.partners_turn ^= cooperation.turn_order == TurnOrder::Alternating;
+        }
+    }
+
+    /// Whether the current position occurred among the recent ones of `position_history`.
+    pub fn is_repeated_position(&self) -> bool {
+        let (current, previous) = self.position_history.split_last().expect("Empty history");
+        previous.contains(current)
+    }
+
+    /// Panics unless the predecessor may move the sokoban of `agent` now.
+    pub fn require_can_step(&self, layout: &Board, agent: u8) {
         // Require game status is correсt
         require!(self.game_status != GameStatus::Finished, "Game is already finished!");
         require!(self.game_status != GameStatus::Unactive, "Game has not been started yet!");
         // Require player valid
-        require!(
-            env::predecessor_account_id() == self.player,
-            "Incorrect predecessor account"
-        );
+        require!(agent < layout.agents, "No such sokoban");
+        let predecessor = env::predecessor_account_id();
+        require!(&predecessor == self.controller(agent), "Incorrect predecessor account");
+        require!(self.is_turn_of(&predecessor), "Not your turn");
+    }
 
-        let new_board = self.board.make_step(direction);
+    /// Moves the sokoban of `agent` using the static layout of the game's board.
+    pub fn make_step(&mut self, layout: &Board, agent: u8, heading: impl Into<Heading>) -> StepResult {
+        self.require_can_step(layout, agent);
+        self.apply_step(layout, agent, heading.into())
+    }
+
+    /// `make_step` for a caller that has already checked `require_can_step` and
+    /// stops once the game is finished.
+    pub(crate) fn apply_step(&mut self, layout: &Board, agent: u8, heading: Heading) -> StepResult {
+        let sokoban = self.state.agent_position(agent as usize);
+        let box_ahead = sokoban
+            .and_then(|sokoban| layout.neighbor(sokoban, heading))
+            .is_some_and(|cell| self.state.has_box(cell));
+        let behind = sokoban
+            .and_then(|sokoban| layout.neighbor(sokoban, heading.opposite()))
+            .filter(|cell| self.state.has_box(*cell));
+        let moved = self.state.make_move(layout, agent as usize, heading);
+        // A pulled box leaves the cell behind the sokoban.
+        let pulled = behind.is_some_and(|cell| !self.state.has_box(cell));
+
+        if moved {
+            self.updated_at = env::block_timestamp();
+            self.move_count += 1;
+            self.position_history.push(self.state.hash);
+            if self.is_repeated_position() {
+                self.repeated_positions += 1;
+            }
+            if self.position_history.len() > POSITION_HISTORY_LEN {
+                self.position_history.remove(0);
+            }
+        }
 
-        self.board = new_board.clone();
-        if new_board.check_if_finished() {
+        if self.state.is_solved(layout) {
         	self.game_status = GameStatus::Finished;
         }
+
+        StepResult { moved, pushed: moved && (box_ahead || pulled) }
+    }
+
+    /// Number of moves that changed the position.
+    pub fn moves(&self) -> u64 {
+        self.move_count
+    }
+
+    /// Cells among `touched` whose state differs from the one they had in `initial`.
+    pub fn changed_cells(&self, layout: &Board, initial: &BoardState, mut touched: Vec<Point>) -> Vec<CellChange> {
+        touched.sort();
+        touched.dedup();
+
+        touched
+            .into_iter()
+            .filter_map(|point| {
+                let state = self.state.state_at(layout, point)?;
+                (initial.state_at(layout, point) != Some(state)).then_some(CellChange { point, state })
+            })
+            .collect()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn get_board() -> Board {
+        let mut board = Board::new(Size { width: 4, height: 2 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 0, y: 1 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 1 }, 1).unwrap();
+
+        board.validate_board()
+    }
+
+    #[test]
+    fn test_game_counts_repeated_positions() {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(0)).build());
+        let board = get_board();
+
+        let mut game = SingleplayerGame::from(0, &board, accounts(0));
+        game.game_status = GameStatus::Running;
+
+        let result = game.make_step(&board, 0, Direction::Down);
+        assert_eq!(result, StepResult { moved: true, pushed: false });
+        assert!(!game.is_repeated_position());
+        game.make_step(&board, 0, Direction::Up);
+        assert!(game.is_repeated_position());
+        let result = game.make_step(&board, 0, Direction::Backward);
+        assert_eq!(result, StepResult::default());
+
+        assert_eq!(game.moves(), 2);
+        assert_eq!(game.repeated_positions, 1);
+
+        let result = game.make_step(&board, 0, Direction::Forward);
+        assert_eq!(result, StepResult { moved: true, pushed: true });
+    }
+
+    #[test]
+    fn test_position_history_is_bounded() {
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(0)).build());
+        let board = get_board();
+
+        let mut game = SingleplayerGame::from(0, &board, accounts(0));
+        game.game_status = GameStatus::Running;
+        for _ in 0..POSITION_HISTORY_LEN {
+            game.make_step(&board, 0, Direction::Down);
+            game.make_step(&board, 0, Direction::Up);
+        }
+
+        assert_eq!(game.moves(), 2 * POSITION_HISTORY_LEN as u64);
+        assert_eq!(game.position_history.len(), POSITION_HISTORY_LEN);
+        assert_eq!(game.repeated_positions, 2 * POSITION_HISTORY_LEN as u32 - 1);
+    }
+
+    #[test]
+    fn test_alternating_partners_take_turns() {
+        let board = Board::from_xsb("#@$. #\n#  .$@#").unwrap().with_agents(2);
+        let mut game = SingleplayerGame::from(0, &board, accounts(0));
+        game.game_status = GameStatus::Running;
+        game.cooperation = Some(Cooperation {
+            partner: accounts(1),
+            turn_order: TurnOrder::Alternating,
+            partners_turn: false,
+        });
+
+        assert_eq!(game.controller(0), &accounts(0));
+        assert_eq!(game.controller(1), &accounts(1));
+        assert!(game.is_turn_of(&accounts(0)));
+        assert!(!game.is_turn_of(&accounts(1)));
+
+        game.end_turn();
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(1)).build());
+        let result = game.make_step(&board, 1, Direction::Backward);
+        assert_eq!(result, StepResult { moved: true, pushed: true });
+        assert_eq!(game.state.agents, vec![Point { x: 4, y: 1 }]);
+        assert!(game.is_turn_of(&accounts(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Incorrect predecessor account")]
+    fn test_partner_cant_move_the_players_sokoban() {
+        let board = Board::from_xsb("#@$. #\n#  .$@#").unwrap().with_agents(2);
+        let mut game = SingleplayerGame::from(0, &board, accounts(0));
+        game.game_status = GameStatus::Running;
+        game.cooperation = Some(Cooperation {
+            partner: accounts(1),
+            turn_order: TurnOrder::FreeForAll,
+            partners_turn: false,
+        });
+
+        testing_env!(VMContextBuilder::new().predecessor_account_id(accounts(1)).build());
+        game.make_step(&board, 0, Direction::Forward);
     }
 }
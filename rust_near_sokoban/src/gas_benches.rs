@@ -0,0 +1,163 @@
+//! Gas used by `step` on square boards, compared with the path it replaced: logging
+//! every row of the board before and after the move, decoding the previous value on
+//! write and reading the game back to return it whole. Only host function costs
+//! (storage, logs, registers) are counted, as the mocked blockchain doesn't meter wasm.
+//!
+//! Run with `cargo test -p rust_near_sokoban gas_benches -- --nocapture` to see the table.
+
+use near_contract_standards::storage_management::StorageManagement;
+use near_sdk::serde_json;
+use near_sdk::test_utils::{accounts, VMContextBuilder};
+use near_sdk::{testing_env, Gas, MockedBlockchain, RuntimeFeesConfig, VMConfig, ONE_NEAR};
+
+use crate::*;
+
+const SIZES: [usize; 4] = [8, 16, 32, 64];
+
+fn set_context(account: AccountId, deposit: Balance) {
+    // The old path logs 2 * height + 2 lines, more than the 100 logs a receipt may
+    // write on a 64x64 board, so the limit is raised to be able to compare.
+    let mut config = VMConfig::test();
+    config.limit_config.max_number_logs = 1_000;
+
+    testing_env!(
+        VMContextBuilder::new()
+            .predecessor_account_id(account)
+            .attached_deposit(deposit)
+            .prepaid_gas(Gas(300_000_000_000_000))
+            .storage_usage(env::storage_usage())
+            .build(),
+        config,
+        RuntimeFeesConfig::test(),
+    );
+}
+
+/// A `size` by `size` board with the sokoban, a box and a goal in the first row.
+fn setup(size: usize) -> (Contract, GameIndex) {
+    // Every run starts from empty storage.
+    env::set_blockchain_interface(MockedBlockchain::new(
+        VMContextBuilder::new().build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Vec::new(),
+        Default::default(),
+        Default::default(),
+        None,
+    ));
+
+    set_context(accounts(0), 0);
+    let mut contract = Contract::new();
+
+    set_context(accounts(0), 10 * ONE_NEAR);
+    contract.storage_deposit(None, None);
+    set_context(accounts(0), 0);
+
+    let size = Size { width: size, height: size };
+    let mut board = Board::new(size);
+    for x in 0..size.width {
+        for y in 0..size.height {
+            board.set_state_at_cell(Point { x, y }, 1).unwrap();
+        }
+    }
+    board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+    board.set_state_at_cell(Point { x: 1, y: 0 }, 2).unwrap();
+    board.set_state_at_cell(Point { x: size.width - 1, y: 0 }, 6).unwrap();
+
+    let index = contract.create_board(board.field.into(), Some(size), None);
+    contract.validate_board(index);
+    let game_index = contract.create_single_game(index, accounts(0));
+    contract.start_single_game(game_index);
+    (contract, game_index)
+}
+
+fn legacy_step(contract: &mut Contract, index: GameIndex, direction: Direction) -> SingleplayerGame {
+    let mut game = contract.internal_get_game(index).unwrap();
+    contract.internal_escrow_before_step(index);
+
+    let layout = contract.internal_get_board(game.board_index).unwrap();
+    env::log_str("Old board");
+    debug_logs(&layout.with_state(&game.state));
+
+    game.make_step(&layout, 0, direction);
+
+    env::log_str("New board");
+    debug_logs(&layout.with_state(&game.state));
+
+    contract.games.insert(&index, &game.into());
+    contract.internal_get_game(index).unwrap()
+}
+
+fn measure<F: FnOnce()>(f: F) -> u64 {
+    let start = env::used_gas();
+    f();
+    (env::used_gas() - start).0
+}
+
+fn step_gas(size: usize) -> u64 {
+    let (mut contract, game_index) = setup(size);
+    measure(|| {
+        let delta = contract.step(game_index, Direction::Forward);
+        env::value_return(&serde_json::to_vec(&delta).unwrap());
+    })
+}
+
+/// Gas of a step in a game that has already walked down and up `moves` times.
+fn long_game_step_gas(size: usize, moves: usize) -> u64 {
+    let (mut contract, game_index) = setup(size);
+    // A new context for every call, as on chain, so logs don't add up. Calls are kept
+    // short enough for their move events to fit the log limit.
+    for _ in 0..moves / 20 {
+        set_context(accounts(0), 0);
+        let directions = [Direction::Down, Direction::Up].repeat(10);
+        contract.steps(game_index, directions);
+    }
+    set_context(accounts(0), 0);
+    measure(|| {
+        let delta = contract.step(game_index, Direction::Forward);
+        env::value_return(&serde_json::to_vec(&delta).unwrap());
+    })
+}
+
+fn legacy_step_gas(size: usize) -> u64 {
+    let (mut contract, game_index) = setup(size);
+    measure(|| {
+        let game = legacy_step(&mut contract, game_index, Direction::Forward);
+        env::value_return(&serde_json::to_vec(&game).unwrap());
+    })
+}
+
+#[test]
+fn bench_step_gas() {
+    println!("{:>7} {:>16} {:>16}", "board", "legacy gas", "step gas");
+
+    for size in SIZES {
+        let legacy = legacy_step_gas(size);
+        let current = step_gas(size);
+        println!("{:>7} {:>16} {:>16}", format!("{}x{}", size, size), legacy, current);
+
+        assert!(current < legacy, "step on {}x{} used {} gas, the old path {}", size, size, current, legacy);
+    }
+}
+
+#[test]
+fn step_gas_doesnt_grow_with_moves() {
+    // Once the position history is full, older moves add nothing to the stored game.
+    let fresh = step_gas(8);
+    let full = long_game_step_gas(8, 2 * POSITION_HISTORY_LEN);
+    let long = long_game_step_gas(8, 5_000);
+    println!("fresh {}, full history {}, after 5000 moves {}", fresh, full, long);
+
+    assert!(long - full < full / 100, "{} gas after 5000 moves, {} with a full history", long, full);
+}
+
+// With `debug-logs` the new path writes the board to the logs as well.
+#[cfg(not(feature = "debug-logs"))]
+#[test]
+fn step_gas_doesnt_depend_on_logs() {
+    // The new path only logs its events, whatever the height of the board.
+    let small = step_gas(8);
+    let large = step_gas(64);
+    let legacy_growth = legacy_step_gas(64) - legacy_step_gas(8);
+
+    assert!(large - small < legacy_growth / 2);
+}
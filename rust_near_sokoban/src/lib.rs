@@ -1,24 +1,59 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, require, AccountId, near_bindgen, BorshStorageKey, PanicOnDefault};
-use near_sdk::collections::{Vector, LookupMap};
+use near_sdk::serde::Serialize;
+use near_sdk::{env, require, AccountId, Balance, near_bindgen, BorshStorageKey, PanicOnDefault, StorageUsage};
+use near_sdk::collections::{Vector, LookupMap, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::Base64VecU8;
 
 use crate::board::*;
 use crate::auxiliary::*;
 use crate::game::*;
+use crate::bid::*;
+use crate::storage::*;
+use crate::events::*;
+use crate::upgrade::*;
+use crate::moderation::*;
+use crate::utils::*;
 
 #[derive(BorshSerialize, BorshStorageKey)]
 pub enum StorageKey {
     Boards,
     Games, 
+    Escrows,
+    Unclaimed,
+    AccountsStorage,
+    BoardCharges,
+    GameCharges,
+    Moderators,
+    BoardVisibility,
+    ModerationLog,
+    BoardsById,
+    GamesById,
+    ActiveGames,
+    MigratedLayouts,
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
-    pub boards: Vector<Board>,
-    pub games: Vector<SingleplayerGame>,
+    pub owner_id: AccountId,
+    pub boards: UnorderedMap<BoardIndex, VersionedBoard>,
+    pub games: LookupMap<GameIndex, VersionedGame>,
+    pub escrows: LookupMap<GameIndex, Escrow>,
+    pub unclaimed: LookupMap<AccountId, Balance>,
+    pub fees_collected: Balance,
+    pub accounts_storage: LookupMap<AccountId, AccountStorage>,
+    pub account_storage_usage: StorageUsage,
+    pub board_charges: LookupMap<BoardIndex, StorageCharge>,
+    pub game_charges: LookupMap<GameIndex, StorageCharge>,
+    pub moderators: UnorderedSet<AccountId>,
+    pub board_visibility: LookupMap<BoardIndex, BoardVisibility>,
+    pub moderation_log: Vector<ModerationRecord>,
+    pub next_board_index: BoardIndex,
+    pub next_game_index: GameIndex,
+    /// Number of games that aren't finished yet, by board.
+    pub active_games: LookupMap<BoardIndex, u64>,
+    /// What is left to move of the state of the first version, see `migrate_step`.
+    pub pending_migration: Option<PendingMigration>,
 }
 
 pub type BoardIndex = u64;
@@ -28,30 +63,72 @@ pub type GameIndex = u64;
 impl Contract {
     #[init]
     pub fn new() -> Self {
-        Self {
-            boards: Vector::new(StorageKey::Boards),
-            games: Vector::new(StorageKey::Games),
-        }
+        let mut this = Self {
+            owner_id: env::predecessor_account_id(),
+            boards: UnorderedMap::new(StorageKey::BoardsById),
+            games: LookupMap::new(StorageKey::GamesById),
+            escrows: LookupMap::new(StorageKey::Escrows),
+            unclaimed: LookupMap::new(StorageKey::Unclaimed),
+            fees_collected: 0,
+            accounts_storage: LookupMap::new(StorageKey::AccountsStorage),
+            account_storage_usage: 0,
+            board_charges: LookupMap::new(StorageKey::BoardCharges),
+            game_charges: LookupMap::new(StorageKey::GameCharges),
+            moderators: UnorderedSet::new(StorageKey::Moderators),
+            board_visibility: LookupMap::new(StorageKey::BoardVisibility),
+            moderation_log: Vector::new(StorageKey::ModerationLog),
+            next_board_index: 0,
+            next_game_index: 0,
+            active_games: LookupMap::new(StorageKey::ActiveGames),
+            pending_migration: None,
+        };
+
+        this.measure_account_storage_usage();
+        Self::internal_write_state_version();
+        this
     }
 
-    pub fn create_board(&mut self, field: Base64VecU8, field_size: Option<Size>) -> BoardIndex {
+    /// Adds a board from its packed field. A classic board unless `options` say otherwise.
+    pub fn create_board(&mut self, field: Base64VecU8, field_size: Option<Size>, options: Option<BoardOptions>) -> BoardIndex {
+        let initial_storage_usage = env::storage_usage();
         let size = field_size.unwrap_or(Size { width: 8, height: 8 });
+        let options = options.unwrap_or_default();
+
+        let board = Board::from_encoded(field.into(), size, options.encoding)
+            .or_panic()
+            .with_ruleset(options.ruleset)
+            .with_teleporters(options.teleporters)
+            .with_colors(options.box_colors, options.goal_colors)
+            .with_one_way(options.one_way)
+            .with_gates(options.gates)
+            .with_agents(options.agents)
+            .with_topology(options.topology)
+            .with_win_condition(options.win_condition);
+        let index = self.next_board_index;
+        self.next_board_index += 1;
+
+        self.boards.insert(&index, &board.into());
+
+        let author = env::predecessor_account_id();
+        self.board_charges.insert(&index, &StorageCharge { account_id: author.clone(), bytes: 0 });
+        let bytes = self.internal_charge_storage(&author, initial_storage_usage, 0);
+
+        SokobanEvent::BoardCreated(BoardCreated { board_index: index, author: &author, size }).emit();
+        self.board_charges.insert(&index, &StorageCharge { account_id: author, bytes });
 
-        let board = Board::from(field, size);
-        let index = self.boards.len();
-
-        self.boards.push(&board);
         index
     }
 
-    pub fn get_board(&self, index: BoardIndex) -> Option<Board> {
-        self.boards.get(index)
+    pub fn get_board(&self, index: BoardIndex) -> Option<BoardView> {
+        self.internal_get_accessible_board(index).map(BoardView::from)
     }
 
     pub fn validate_board(&mut self, index: BoardIndex) {
-        let board = self.get_board(index).expect("No board");
+        let board = self.internal_get_accessible_board(index).expect("No board");
         let new_board = board.validate_board();
-        self.boards.replace(index, &new_board);
+
+        SokobanEvent::BoardValidated(BoardValidated { board_index: index, is_valid: new_board.is_valid }).emit();
+        self.boards.insert(&index, &new_board.into());
     }
 
     pub fn create_single_game(
@@ -59,44 +136,307 @@ impl Contract {
         index: BoardIndex, 
         player: AccountId,
     ) -> GameIndex {
-        let board = self.get_board(index).expect("No board");
-        require!(board.is_valid, "Invalid board to play!");
-
-        let board = board.clone();
+        let initial_storage_usage = env::storage_usage();
+        let game_index = self.internal_create_single_game(index, player, None);
+        self.internal_charge_game_storage(game_index, initial_storage_usage);
+        game_index
+    }
 
-        let game = SingleplayerGame::from(board, player);
-        let index = self.games.len();
+    /// Creates a game on a board with several sokobans, shared by two accounts:
+    /// `player` moves agent 0 and `partner` every other agent.
+    pub fn create_cooperative_game(
+        &mut self,
+        index: BoardIndex,
+        player: AccountId,
+        partner: AccountId,
+        turn_order: TurnOrder,
+    ) -> GameIndex {
+        require!(player != partner, "The partner must be another account");
 
-        self.games.push(&game);
-        index
+        let initial_storage_usage = env::storage_usage();
+        let cooperation = Cooperation { partner, turn_order, partners_turn: false };
+        let game_index = self.internal_create_single_game(index, player, Some(cooperation));
+        self.internal_charge_game_storage(game_index, initial_storage_usage);
+        game_index
     }
 
     pub fn get_single_game(&self, index: GameIndex) -> Option<SingleplayerGame> {
-        self.games.get(index)
+        self.internal_get_game(index)
+    }
+
+    /// Renders the current position of a game over its board.
+    pub fn get_single_game_board(&self, index: GameIndex) -> Option<BoardView> {
+        let game = self.internal_get_game(index)?;
+        let layout = self.internal_get_board(game.board_index)?;
+        Some(layout.with_state(&game.state).into())
     }
 
     pub fn start_single_game(&mut self, index: GameIndex) {
+        require!(!self.escrows.contains_key(&index), "Wagered games are started by accept_wager");
+
         let mut game = self.get_single_game(index).expect("Game doesn't exist");
+        require!(game.game_status == GameStatus::Unactive, "Game has already been started!");
+        require!(env::predecessor_account_id() == game.player, "Only the player can start the game");
         game.game_status = GameStatus::Running;
-        self.games.replace(index, &game);
+        game.updated_at = env::block_timestamp();
+        game.version += 1;
+
+        SokobanEvent::GameStarted(GameStarted { game_index: index, player: &game.player }).emit();
+        self.games.insert(&index, &game.into());
+    }
+
+    /// Applies a move and returns what changed. Use `get_single_game` for the full game.
+    pub fn step(&mut self, index: GameIndex, direction: Direction) -> StepDelta {
+        self.internal_steps(index, 0, &[direction.into()])
+    }
+
+    /// Applies several moves at once, stopping when the game is finished.
+    pub fn steps(&mut self, index: GameIndex, directions: Vec<Direction>) -> StepDelta {
+        self.agent_steps(index, 0, directions)
+    }
+
+    /// Like `steps`, for the sokoban of `agent` on a board with several.
+    pub fn agent_steps(&mut self, index: GameIndex, agent: u8, directions: Vec<Direction>) -> StepDelta {
+        let headings: Vec<Heading> = directions.into_iter().map(Heading::from).collect();
+        self.internal_checked_steps(index, agent, &headings)
+    }
+
+    /// Applies a move on a hex board, see `Topology::Hex`.
+    pub fn hex_step(&mut self, index: GameIndex, direction: HexDirection) -> StepDelta {
+        self.internal_steps(index, 0, &[direction.into()])
+    }
+
+    /// Applies several moves on a hex board at once, stopping when the game is finished.
+    pub fn hex_steps(&mut self, index: GameIndex, directions: Vec<HexDirection>) -> StepDelta {
+        self.agent_hex_steps(index, 0, directions)
+    }
+
+    /// Like `hex_steps`, for the sokoban of `agent` on a board with several.
+    pub fn agent_hex_steps(&mut self, index: GameIndex, agent: u8, directions: Vec<HexDirection>) -> StepDelta {
+        let headings: Vec<Heading> = directions.into_iter().map(Heading::from).collect();
+        self.internal_checked_steps(index, agent, &headings)
+    }
+
+    /// Deletes a game that is finished or expired and frees the storage of its payer.
+    /// A game expires when its wager has been settled, or when it has no wager and
+    /// nobody has played it for `GAME_EXPIRY_TIMEOUT`.
+    pub fn delete_game(&mut self, index: GameIndex) {
+        let game = self.internal_get_game(index).expect("Game doesn't exist");
+        require!(env::predecessor_account_id() == game.player, "Incorrect predecessor account");
+
+        let is_expired = match self.escrows.get(&index) {
+            Some(escrow) => {
+                require!(escrow.status == EscrowStatus::Settled, "Wager is not settled yet");
+                true
+            },
+            None => env::block_timestamp() > game.updated_at + GAME_EXPIRY_TIMEOUT,
+        };
+
+        let is_finished = game.game_status == GameStatus::Finished;
+        require!(is_finished || is_expired, "Game is still active");
+
+        if !is_finished {
+            self.internal_release_active_game(game.board_index);
+        }
+
+        self.games.remove(&index);
+        self.escrows.remove(&index);
+        if let Some(charge) = self.game_charges.remove(&index) {
+            self.internal_release_storage(&charge);
+        }
+
+        SokobanEvent::GameDeleted(GameDeleted { game_index: index, player: &game.player }).emit();
+    }
+
+    /// Deletes a board nobody is playing anymore and frees the storage of its author.
+    pub fn delete_board(&mut self, index: BoardIndex) {
+        require!(self.boards.get(&index).is_some(), "No board");
+
+        let charge = self.board_charges.get(&index);
+        let author = env::predecessor_account_id();
+        // Boards migrated from the first version have no recorded author; the owner deletes those.
+        require!(
+            charge.as_ref().map_or(&self.owner_id, |charge| &charge.account_id) == &author,
+            "Only the author can delete the board"
+        );
+        require!(self.active_games.get(&index).unwrap_or(0) == 0, "Board has active games");
+
+        self.boards.remove(&index);
+        self.board_visibility.remove(&index);
+        self.active_games.remove(&index);
+        if let Some(charge) = self.board_charges.remove(&index) {
+            self.internal_release_storage(&charge);
+        }
+
+        SokobanEvent::BoardDeleted(BoardDeleted { board_index: index, author: &author }).emit();
+    }
+}
+
+impl Contract {
+    pub(crate) fn internal_get_board(&self, index: BoardIndex) -> Option<Board> {
+        self.boards.get(&index).map(Into::into)
+    }
+
+    pub(crate) fn internal_get_game(&self, index: GameIndex) -> Option<SingleplayerGame> {
+        self.games.get(&index).map(Into::into)
+    }
+
+    /// `internal_steps` with the limits on the number of moves of one call.
+    pub(crate) fn internal_checked_steps(&mut self, index: GameIndex, agent: u8, headings: &[Heading]) -> StepDelta {
+        require!(!headings.is_empty(), "No moves given");
+        require!(headings.len() <= MAX_STEPS_PER_CALL, "Too many moves in one call");
+        self.internal_steps(index, agent, headings)
+    }
+
+    pub(crate) fn internal_steps(&mut self, index: GameIndex, agent: u8, headings: &[Heading]) -> StepDelta {
+        let mut game = self.internal_get_game(index).expect("Game doesn't exist");
+
+        self.internal_escrow_before_step(index);
+
+        let layout = self.internal_get_board(game.board_index).expect("No board");
+        require!(
+            headings.iter().all(|heading| layout.accepts(*heading)),
+            "Hex boards take hex moves, other boards square ones"
+        );
+        // Checked once for the whole call: the loop stops when the game is finished,
+        // and the turn only passes after it.
+        game.require_can_step(&layout, agent);
+        if cfg!(feature = "debug-logs") {
+            env::log_str("Old board");
+            debug_logs(&layout.with_state(&game.state));
+        }
+
+        let initial = game.state.clone();
+        let mut touched = Vec::new();
+        let mut pushed = false;
+
+        for &heading in headings {
+            if game.game_status == GameStatus::Finished {
+                break;
+            }
+
+            let previous = game.state.clone();
+            let result = game.apply_step(&layout, agent, heading);
+            if !result.moved {
+                continue;
+            }
+
+            touched.extend(game.state.moved_points(&previous));
+            // Gates open and close without a piece moving onto them.
+            touched.extend(layout.gates.iter().map(|&(_, gate)| gate));
+            pushed |= result.pushed;
+
+            SokobanEvent::Move(Move {
+                game_index: index,
+                player: game.controller(agent),
+                agent: (layout.agents > 1).then_some(agent),
+                direction: heading,
+                pushed: result.pushed,
+                sokoban_position: game.state.agent_position(agent as usize).expect("No such sokoban"),
+            }).emit();
+        }
+
+        if cfg!(feature = "debug-logs") {
+            env::log_str("New board");
+            debug_logs(&layout.with_state(&game.state));
+        }
+
+        // The game was running before the loop, so this is the move that finished it.
+        let is_finished = game.game_status == GameStatus::Finished;
+        if is_finished {
+            SokobanEvent::GameFinished(GameFinished {
+                game_index: index,
+                player: &game.player,
+                moves: game.moves(),
+            }).emit();
+            self.internal_release_active_game(game.board_index);
+        }
+
+        let moved = !touched.is_empty();
+        if moved {
+            game.end_turn();
+        }
+        let is_changed = moved || is_finished;
+        if is_changed {
+            game.version += 1;
+        }
+
+        let delta = StepDelta {
+            version: game.version,
+            changes: game.changed_cells(&layout, &initial, touched),
+            moved,
+            pushed,
+            sokoban_position: game.state.agent_position(agent as usize).expect("No such sokoban"),
+            game_status: game.game_status,
+        };
+
+        if is_changed {
+            self.internal_write_game(index, game.into());
+        }
+        if is_finished {
+            self.internal_escrow_on_finish(index);
+        }
+
+        delta
+    }
+
+    /// Overwrites a game. Unlike `LookupMap::insert` it doesn't decode the previous
+    /// value, which matters on the `step` path.
+    pub(crate) fn internal_write_game(&mut self, index: GameIndex, game: VersionedGame) {
+        self.games.insert_raw(&index.try_to_vec().unwrap(), &game.try_to_vec().unwrap());
     }
 
-    pub fn step(&mut self, index: GameIndex, direction: Direction) -> SingleplayerGame {
-        let mut game = self.games
-            .get(index)
-            .expect("Game doesn't exist");
+    pub(crate) fn internal_release_active_game(&mut self, board_index: BoardIndex) {
+        let active_games = self.active_games.get(&board_index).unwrap_or(0);
+        if active_games > 1 {
+            self.active_games.insert(&board_index, &(active_games - 1));
+        } else {
+            self.active_games.remove(&board_index);
+        }
+    }
+
+    pub(crate) fn internal_create_single_game(
+        &mut self,
+        index: BoardIndex,
+        player: AccountId,
+        cooperation: Option<Cooperation>,
+    ) -> GameIndex {
+        let board = self.internal_get_accessible_board(index).expect("No board");
+        require!(board.is_valid, "Invalid board to play!");
+        require!(cooperation.is_none() || board.agents > 1, "The board has a single sokoban");
+
+        let mut game = SingleplayerGame::from(index, &board, player);
+        game.cooperation = cooperation;
+        let game_index = self.next_game_index;
+        self.next_game_index += 1;
+
+        SokobanEvent::GameCreated(GameCreated {
+            game_index,
+            board_index: index,
+            player: &game.player,
+        }).emit();
+        self.games.insert(&game_index, &game.into());
 
-        let old_board = game.board.clone();
-        env::log_str("Old board");
-        old_board.debug_logs();
+        let active_games = self.active_games.get(&index).unwrap_or(0);
+        self.active_games.insert(&index, &(active_games + 1));
 
-        game.make_step(direction);
+        game_index
+    }
 
-        env::log_str("New board");
-        game.board.debug_logs();
+    /// Charges the predecessor for everything written since `initial_storage_usage`
+    /// while creating the game at `index`, and for what the game may grow by: its
+    /// position history and, with a wager, the balances of both accounts if paying
+    /// them out fails.
+    pub(crate) fn internal_charge_game_storage(&mut self, index: GameIndex, initial_storage_usage: StorageUsage) {
+        let mut reserved = GAME_HISTORY_RESERVE;
+        if self.escrows.contains_key(&index) {
+            reserved += 2 * self.measure_unclaimed_storage_usage();
+        }
 
-        self.games.replace(index, &game);
-        return self.games.get(index).unwrap();
+        let payer = env::predecessor_account_id();
+        self.game_charges.insert(&index, &StorageCharge { account_id: payer.clone(), bytes: 0 });
+        let bytes = self.internal_charge_storage(&payer, initial_storage_usage, reserved);
+        self.game_charges.insert(&index, &StorageCharge { account_id: payer, bytes });
     }
 }
 
@@ -104,27 +444,37 @@ impl Contract {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{testing_env, VMContext};
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, VMContext, ONE_NEAR};
+    use near_contract_standards::storage_management::StorageManagement;
 
     fn get_context(is_view: bool) -> VMContext {
         VMContextBuilder::new().is_view(is_view).build()
     }
 
+    fn register(contract: &mut Contract, account: AccountId) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account.clone())
+            .attached_deposit(ONE_NEAR)
+            .storage_usage(env::storage_usage())
+            .build());
+        contract.storage_deposit(None, None);
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account)
+            .storage_usage(env::storage_usage())
+            .build());
+    }
+
     fn get_context_account(account: AccountId) -> near_sdk::VMContext {
         VMContextBuilder::new()
             .predecessor_account_id(account)
             .build()
     }
 
-    impl PartialEq for Board {
-        fn eq(&self, other: &Self) -> bool {
-            self.field == other.field && 
-            self.is_valid == other.is_valid && 
-            self.sokoban_position == other.sokoban_position && 
-            self.size == other.size && 
-            self.field_len == other.field_len
-        }
+    /// Logs of the events, without the boards written under `debug-logs`.
+    fn event_logs() -> Vec<String> {
+        get_logs().into_iter().filter(|log| log.starts_with("EVENT_JSON:")).collect()
     }
 
     #[test]
@@ -139,7 +489,7 @@ mod tests {
         let context = get_context(false);
         testing_env!(context);
         let mut contract = Contract::new();
-
+        register(&mut contract, accounts(1));
 
         let width: usize = 8;
         let height: usize = 8;
@@ -147,7 +497,7 @@ mod tests {
 
         let mut field = vec![0u8; field_len];
         field[0] = 50;
-        let index = contract.create_board(field.clone().into(), None);
+        let index = contract.create_board(field.clone().into(), None, None);
         assert_eq!(index, 0);
 
         testing_env!(get_context(true));
@@ -160,14 +510,16 @@ mod tests {
         let context = get_context(false);
         testing_env!(context);
         let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
 
         let mut board = Board::new(Size { width: 2, height: 1 });
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 1);
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 1).unwrap();
 
         let index = contract.create_board(
-            board.field.clone(), 
-            Some(Size { width: 2, height: 1 })
+            board.field.clone().into(), 
+            Some(Size { width: 2, height: 1 }),
+            None,
         );
 
         let game_index = contract.create_single_game(index, accounts(0));
@@ -178,7 +530,8 @@ mod tests {
         assert!(contract.get_single_game(game_index + 1).is_none());
         assert!(game.is_some());
         assert_eq!(game.as_ref().unwrap().player, accounts(0));
-        assert_eq!(game.unwrap().board.field, board.field);
+        assert_eq!(game.unwrap().board_index, index);
+        assert_eq!(contract.get_single_game_board(game_index).unwrap().field.0, board.field);
     }
 
     #[test]
@@ -186,34 +539,483 @@ mod tests {
         let context = get_context(false);
         testing_env!(context);
         let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
 
         let mut board = Board::new(Size { width: 4, height: 2 });
-        board.set_state_at_cell(Point { x: 0, y: 0 }, 1);
-        board.set_state_at_cell(Point { x: 1, y: 0 }, 4);
-        board.set_state_at_cell(Point { x: 2, y: 0 }, 2);
-        board.set_state_at_cell(Point { x: 3, y: 0 }, 6);
-        board.set_state_at_cell(Point { x: 1, y: 1 }, 1);
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 1).unwrap();
 
         let index = contract.create_board(
-            board.field.clone(), 
-            Some(Size { width: 4, height: 2 })
+            board.field.clone().into(), 
+            Some(Size { width: 4, height: 2 }),
+            None,
         );
 
         let game_index = contract.create_single_game(index, accounts(0));
         let game = contract.get_single_game(game_index);
         assert_eq!(game.unwrap().game_status, GameStatus::Unactive);
 
+        testing_env!(get_context_account(accounts(0)));
         contract.start_single_game(game_index);
         let game = contract.get_single_game(game_index);
         assert_eq!(game.unwrap().game_status, GameStatus::Running);
 
         testing_env!(get_context_account(accounts(0)));
-        contract.step(game_index, Direction::Forward);
+        let delta = contract.step(game_index, Direction::Forward);
+        assert_eq!(delta, StepDelta {
+            version: 2,
+            changes: vec![
+                CellChange { point: Point { x: 1, y: 0 }, state: 1 },
+                CellChange { point: Point { x: 2, y: 0 }, state: 4 },
+                CellChange { point: Point { x: 3, y: 0 }, state: 3 },
+            ],
+            moved: true,
+            pushed: true,
+            sokoban_position: Point { x: 2, y: 0 },
+            game_status: GameStatus::Finished,
+        });
+
         let game = contract.get_single_game(game_index);
         assert_eq!(game.unwrap().game_status, GameStatus::Finished);
+
+        let logs = event_logs();
+        assert_eq!(logs.len(), 2);
+        assert!(logs[0].starts_with(r#"EVENT_JSON:{"standard":"sokoban","version":"1.0.0","event":"move""#));
+        assert!(logs[0].contains(r#""direction":"Forward","pushed":true"#));
+        assert!(logs[1].contains(r#""event":"game_finished""#));
+        assert!(logs[1].contains(r#""moves":1"#));
+    }
+
+    #[test]
+    fn test_steps_returns_net_changes() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let mut board = Board::new(Size { width: 4, height: 2 });
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 1).unwrap();
+
+        let index = contract.create_board(board.field.into(), Some(Size { width: 4, height: 2 }), None);
+        contract.validate_board(index);
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+
+        let delta = contract.steps(
+            game_index,
+            vec![Direction::Down, Direction::Up, Direction::Forward, Direction::Backward],
+        );
+
+        assert_eq!(delta.version, 2);
+        assert_eq!(delta.changes, vec![
+            CellChange { point: Point { x: 1, y: 0 }, state: 1 },
+            CellChange { point: Point { x: 2, y: 0 }, state: 4 },
+            CellChange { point: Point { x: 3, y: 0 }, state: 3 },
+        ]);
+        assert!(delta.pushed);
+        assert_eq!(delta.game_status, GameStatus::Finished);
+
+        let game = contract.get_single_game(game_index).unwrap();
+        assert_eq!(game.version, 2);
+        assert_eq!(game.moves(), 3);
+    }
+
+    #[test]
+    fn test_step_on_ice_reports_where_the_box_stopped() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let board = Board::from_xsb("@$~~.").unwrap();
+        let options = BoardOptions { encoding: CellEncoding::Extended, ruleset: Ruleset::Ice, ..Default::default() };
+        let index = contract.create_board(board.field.into(), Some(board.size), Some(options));
+        assert_eq!(contract.get_board(index).unwrap().ruleset, Ruleset::Ice);
+
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+
+        let delta = contract.step(game_index, Direction::Forward);
+        assert_eq!(delta.changes, vec![
+            CellChange { point: Point { x: 0, y: 0 }, state: Cell::Floor.into() },
+            CellChange { point: Point { x: 1, y: 0 }, state: Cell::Sokoban.into() },
+            CellChange { point: Point { x: 4, y: 0 }, state: Cell::BoxOnGoal.into() },
+        ]);
+        assert_eq!(delta.game_status, GameStatus::Finished);
+    }
+
+    #[test]
+    fn test_reverse_game_pulls_boxes_back() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let board = Board::from_xsb(".$@ ").unwrap();
+        let options = BoardOptions { ruleset: Ruleset::Reverse, ..Default::default() };
+        let index = contract.create_board(board.field.into(), Some(board.size), Some(options));
+
+        let game_index = contract.create_single_game(index, accounts(1));
+        let start = Board::try_from(contract.get_single_game_board(game_index).unwrap()).unwrap();
+        assert_eq!(start.get_cell(Point { x: 0, y: 0 }), Some(Cell::BoxOnGoal));
+        contract.start_single_game(game_index);
+
+        // The game is won once the box is back, the last move isn't played.
+        let delta = contract.steps(game_index, vec![Direction::Backward, Direction::Forward, Direction::Forward]);
+        assert!(delta.pushed);
+        assert_eq!(delta.changes, vec![
+            CellChange { point: Point { x: 0, y: 0 }, state: Cell::Goal.into() },
+            CellChange { point: Point { x: 1, y: 0 }, state: Cell::Box.into() },
+        ]);
+        assert_eq!(delta.sokoban_position, Point { x: 2, y: 0 });
+        assert_eq!(delta.game_status, GameStatus::Finished);
+    }
+
+    #[test]
+    fn test_toroidal_board_steps_across_the_edge() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let board = Board::from_xsb("$. @").unwrap();
+        let options = BoardOptions { topology: Topology::Toroidal, ..Default::default() };
+        let index = contract.create_board(board.field.into(), Some(board.size), Some(options));
+        assert_eq!(contract.get_board(index).unwrap().topology, Topology::Toroidal);
+
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+        let delta = contract.step(game_index, Direction::Forward);
+        assert!(delta.pushed);
+        assert_eq!(delta.sokoban_position, Point { x: 0, y: 0 });
+        assert_eq!(delta.game_status, GameStatus::Finished);
+    }
+
+    #[test]
+    fn test_escape_board_is_won_at_the_exit() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let cells = [Cell::Sokoban, Cell::Box, Cell::Goal, Cell::Floor, Cell::Floor, Cell::Exit];
+        let field: Vec<u8> = cells.into_iter().map(u8::from).collect();
+        let options = BoardOptions {
+            encoding: CellEncoding::Extended,
+            win_condition: WinCondition::ReachExit,
+            ..Default::default()
+        };
+        let index = contract.create_board(field.into(), Some(Size { width: 3, height: 2 }), Some(options));
+        assert_eq!(contract.get_board(index).unwrap().win_condition, WinCondition::ReachExit);
+
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+
+        // The box on its goal isn't enough, the sokoban has to get out.
+        let delta = contract.step(game_index, Direction::Forward);
+        assert_eq!(delta.game_status, GameStatus::Running);
+        let delta = contract.steps(game_index, vec![Direction::Down, Direction::Forward]);
+        assert_eq!(delta.sokoban_position, Point { x: 2, y: 1 });
+        assert_eq!(delta.game_status, GameStatus::Finished);
+    }
+
+    #[test]
+    fn test_hex_board_takes_hex_moves() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let board = Board::from_hex_xsb("# # # #\n # @ # #\n# # $ #\n # # . #\n# # # #").unwrap();
+        let options = BoardOptions { topology: Topology::Hex, ..Default::default() };
+        let index = contract.create_board(board.field.into(), Some(board.size), Some(options));
+
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+        let delta = contract.hex_steps(game_index, vec![HexDirection::East, HexDirection::SouthEast]);
+        assert!(delta.pushed);
+        assert_eq!(delta.game_status, GameStatus::Finished);
+        assert!(get_logs().iter().any(|log| log.contains(r#""direction":"SouthEast""#)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Hex boards take hex moves, other boards square ones")]
+    fn test_square_board_rejects_hex_moves() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (_, game_index) = setup_game(&mut contract);
+        contract.start_single_game(game_index);
+
+        contract.hex_step(game_index, HexDirection::East);
+    }
+
+    #[test]
+    fn test_colored_boxes_finish_on_their_goals() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let board = Board::from_xsb("@$.$.").unwrap();
+        let options = BoardOptions {
+            box_colors: vec![(Point { x: 1, y: 0 }, 1)],
+            goal_colors: vec![(Point { x: 4, y: 0 }, 1)],
+            ..Default::default()
+        };
+        let index = contract.create_board(board.field.into(), Some(board.size), Some(options));
+        assert_eq!(contract.get_board(index).unwrap().goal_colors, vec![(Point { x: 4, y: 0 }, 1)]);
+
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+
+        // The plain goal in the middle doesn't take the coloured box.
+        let delta = contract.step(game_index, Direction::Forward);
+        assert_eq!(delta.game_status, GameStatus::Running);
+        assert_eq!(contract.get_single_game(game_index).unwrap().state.box_colors, vec![(Point { x: 2, y: 0 }, 1)]);
+    }
+
+    #[test]
+    fn test_step_reports_gates_that_open() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let mut board = Board::new_with_encoding(Size { width: 5, height: 1 }, CellEncoding::Extended);
+        let cells = [Cell::Sokoban, Cell::Box, Cell::Plate, Cell::Gate, Cell::Goal];
+        for (x, cell) in cells.into_iter().enumerate() {
+            board.set_cell(Point { x, y: 0 }, cell).unwrap();
+        }
+        let gates = vec![(Point { x: 2, y: 0 }, Point { x: 3, y: 0 })];
+        let options = BoardOptions { encoding: CellEncoding::Extended, gates: gates.clone(), ..Default::default() };
+        let index = contract.create_board(board.field.into(), Some(board.size), Some(options));
+        assert_eq!(contract.get_board(index).unwrap().gates, gates);
+
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+
+        let delta = contract.step(game_index, Direction::Forward);
+        assert_eq!(delta.changes, vec![
+            CellChange { point: Point { x: 0, y: 0 }, state: Cell::Floor.into() },
+            CellChange { point: Point { x: 1, y: 0 }, state: Cell::Sokoban.into() },
+            CellChange { point: Point { x: 2, y: 0 }, state: Cell::BoxOnPlate.into() },
+            CellChange { point: Point { x: 3, y: 0 }, state: Cell::OpenGate.into() },
+        ]);
+    }
+
+    #[test]
+    fn test_create_board_with_teleporters() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let board = Board::from_xsb("@$^.^").unwrap();
+        let pairs = vec![(Point { x: 2, y: 0 }, Point { x: 4, y: 0 })];
+        let options = BoardOptions { encoding: CellEncoding::Extended, teleporters: pairs.clone(), ..Default::default() };
+        let index = contract.create_board(board.field.clone().into(), Some(board.size), Some(options));
+        assert_eq!(contract.get_board(index).unwrap().teleporters, pairs);
+        assert!(contract.get_board(index).unwrap().is_valid);
+
+        let options = BoardOptions { encoding: CellEncoding::Extended, ..Default::default() };
+        let unpaired = contract.create_board(board.field.into(), Some(board.size), Some(options));
+        assert!(!contract.get_board(unpaired).unwrap().is_valid);
+
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+        let delta = contract.step(game_index, Direction::Forward);
+        assert_eq!(delta.changes, vec![
+            CellChange { point: Point { x: 0, y: 0 }, state: Cell::Floor.into() },
+            CellChange { point: Point { x: 1, y: 0 }, state: Cell::Sokoban.into() },
+            CellChange { point: Point { x: 4, y: 0 }, state: Cell::BoxOnTeleporter.into() },
+        ]);
+    }
+
+    #[test]
+    fn test_cooperative_game_moves_both_sokobans() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        register(&mut contract, accounts(1));
+
+        let board = Board::from_xsb("#@$. #\n#  .$@#").unwrap();
+        let options = BoardOptions { agents: 2, ..Default::default() };
+        let index = contract.create_board(board.field.into(), Some(board.size), Some(options));
+        assert!(contract.get_board(index).unwrap().is_valid);
+
+        let game_index = contract.create_cooperative_game(index, accounts(1), accounts(2), TurnOrder::Alternating);
+        contract.start_single_game(game_index);
+        let delta = contract.step(game_index, Direction::Forward);
+        assert_eq!(delta.sokoban_position, Point { x: 2, y: 0 });
+        assert_eq!(delta.game_status, GameStatus::Running);
+
+        testing_env!(get_context_account(accounts(2)));
+        let delta = contract.agent_steps(game_index, 1, vec![Direction::Backward]);
+        assert_eq!(delta.sokoban_position, Point { x: 4, y: 1 });
+        assert_eq!(delta.game_status, GameStatus::Finished);
+        assert!(event_logs()[0].contains(r#""player":"charlie","agent":1"#));
+    }
+
+    #[test]
+    fn test_blocked_step_keeps_version() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (_, game_index) = setup_game(&mut contract);
+        contract.start_single_game(game_index);
+
+        let delta = contract.step(game_index, Direction::Up);
+        assert!(!delta.moved);
+        assert!(delta.changes.is_empty());
+        assert_eq!(delta.version, 1);
+    }
+
+    #[test]
+    fn test_written_game_reads_back() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (_, game_index) = setup_game(&mut contract);
+
+        let mut game = contract.get_single_game(game_index).unwrap();
+        game.version = 7;
+        contract.internal_write_game(game_index, game.into());
+        let read: SingleplayerGame = contract.games.get(&game_index).unwrap().into();
+        assert_eq!(read.version, 7);
+    }
+
+    fn setup_game(contract: &mut Contract) -> (BoardIndex, GameIndex) {
+        register(contract, accounts(1));
+
+        let mut board = Board::new(Size { width: 3, height: 1 });
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 6).unwrap();
+
+        let index = contract.create_board(board.field.into(), Some(Size { width: 3, height: 1 }), None);
+        contract.validate_board(index);
+        let game_index = contract.create_single_game(index, accounts(1));
+        (index, game_index)
+    }
+
+    fn set_account_and_time(account: AccountId, timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account)
+            .block_timestamp(timestamp)
+            .storage_usage(env::storage_usage())
+            .build());
+    }
+
+    #[test]
+    fn test_delete_finished_game_and_board() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (index, game_index) = setup_game(&mut contract);
+
+        contract.start_single_game(game_index);
+        contract.step(game_index, Direction::Forward);
+        contract.delete_game(game_index);
+        assert!(contract.get_single_game(game_index).is_none());
+
+        contract.delete_board(index);
+        assert!(contract.get_board(index).is_none());
+        let storage = contract.accounts_storage.get(&accounts(1)).unwrap();
+        assert_eq!(storage.used_bytes, contract.account_storage_usage);
+
+        let next_index = contract.create_board(vec![0u8; 32].into(), None, None);
+        assert_eq!(next_index, index + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Game is still active")]
+    fn test_delete_running_game() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (_, game_index) = setup_game(&mut contract);
+
+        contract.start_single_game(game_index);
+        contract.delete_game(game_index);
+    }
+
+    #[test]
+    fn test_delete_expired_game() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (index, game_index) = setup_game(&mut contract);
+
+        set_account_and_time(accounts(1), GAME_EXPIRY_TIMEOUT + 1);
+        contract.delete_game(game_index);
+        assert_eq!(contract.active_games.get(&index), None);
+        contract.delete_board(index);
+    }
+
+    #[test]
+    #[should_panic(expected = "Game has already been started!")]
+    fn test_restart_finished_game() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (_, game_index) = setup_game(&mut contract);
+
+        contract.start_single_game(game_index);
+        contract.step(game_index, Direction::Forward);
+        assert_eq!(contract.get_single_game(game_index).unwrap().game_status, GameStatus::Finished);
+        contract.start_single_game(game_index);
+    }
+
+    #[test]
+    #[should_panic(expected = "Game is already finished!")]
+    fn test_steps_on_finished_game() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (_, game_index) = setup_game(&mut contract);
+
+        contract.start_single_game(game_index);
+        contract.step(game_index, Direction::Forward);
+
+        testing_env!(get_context_account(accounts(2)));
+        contract.steps(game_index, vec![Direction::Forward]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the player can start the game")]
+    fn test_start_game_of_other_player() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (_, game_index) = setup_game(&mut contract);
+
+        testing_env!(get_context_account(accounts(2)));
+        contract.start_single_game(game_index);
+    }
+
+    #[test]
+    #[should_panic(expected = "Board has active games")]
+    fn test_delete_board_with_active_games() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (index, _) = setup_game(&mut contract);
+
+        contract.delete_board(index);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the author can delete the board")]
+    fn test_delete_board_by_other_account() {
+        testing_env!(get_context(false));
+        let mut contract = Contract::new();
+        let (index, game_index) = setup_game(&mut contract);
+
+        set_account_and_time(accounts(1), GAME_EXPIRY_TIMEOUT + 1);
+        contract.delete_game(game_index);
+
+        set_account_and_time(accounts(2), GAME_EXPIRY_TIMEOUT + 1);
+        contract.delete_board(index);
     }
 }
 
 pub mod board;
 pub mod auxiliary;
-pub mod game;
\ No newline at end of file
+pub mod game;
+pub mod bid;
+pub mod utils;
+pub mod storage;
+pub mod events;
+pub mod upgrade;
+pub mod moderation;
+pub mod svg;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod gas_benches;
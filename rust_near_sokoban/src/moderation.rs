@@ -0,0 +1,349 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, require, AccountId, Timestamp};
+
+use crate::*;
+
+/// Where a board can be seen. Only boards that aren't `Listed` are stored.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub enum BoardVisibility {
+    /// Shown in listings.
+    Listed,
+    /// Left out of listings but can still be opened and played by index.
+    Unlisted,
+    /// Can't be opened or played. Games that are already running can be finished.
+    Hidden,
+    /// Like `Hidden`, but can't be restored.
+    Deleted,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ModerationAction {
+    Hide,
+    Unlist,
+    Delete,
+    Restore,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ModerationReason {
+    Offensive,
+    Broken,
+    Spam,
+    Duplicate,
+    Other,
+}
+
+/// Entry of the moderation audit log.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ModerationRecord {
+    pub board_index: BoardIndex,
+    pub moderator: AccountId,
+    pub action: ModerationAction,
+    pub reason: ModerationReason,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BoardListing {
+    pub board_index: BoardIndex,
+    pub board: BoardView,
+}
+
+/// A page of `get_boards`.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BoardPage {
+    pub boards: Vec<BoardListing>,
+    /// Where the next page starts, `None` once every board has been looked at.
+    pub next_index: Option<BoardIndex>,
+}
+
+impl ModerationAction {
+    fn visibility(&self) -> BoardVisibility {
+        match self {
+            ModerationAction::Hide => BoardVisibility::Hidden,
+            ModerationAction::Unlist => BoardVisibility::Unlisted,
+            ModerationAction::Delete => BoardVisibility::Deleted,
+            ModerationAction::Restore => BoardVisibility::Listed,
+        }
+    }
+}
+
+impl BoardVisibility {
+    pub fn is_accessible(&self) -> bool {
+        matches!(self, BoardVisibility::Listed | BoardVisibility::Unlisted)
+    }
+}
+
+pub const DEFAULT_LISTING_LIMIT: u64 = 50;
+/// Board indices `get_boards` looks at in one call, listed or not.
+pub const MAX_LISTING_SCAN: u64 = 200;
+
+#[near_bindgen]
+impl Contract {
+    pub fn add_moderator(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.moderators.insert(&account_id);
+    }
+
+    pub fn remove_moderator(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.moderators.remove(&account_id);
+    }
+
+    pub fn get_moderators(&self) -> Vec<AccountId> {
+        self.moderators.to_vec()
+    }
+
+    pub fn is_moderator(&self, account_id: AccountId) -> bool {
+        account_id == self.owner_id || self.moderators.contains(&account_id)
+    }
+
+    /// Changes the visibility of a board and records why in the audit log. The
+    /// moderator's storage deposit pays for the record.
+    pub fn moderate_board(&mut self, index: BoardIndex, action: ModerationAction, reason: ModerationReason) {
+        let initial_storage_usage = env::storage_usage();
+        let moderator = env::predecessor_account_id();
+        require!(self.is_moderator(moderator.clone()), "Only moderators can moderate boards");
+
+        let visibility = self.get_board_visibility(index).expect("No board");
+        require!(visibility != BoardVisibility::Deleted, "Board is deleted");
+
+        match action.visibility() {
+            BoardVisibility::Listed => self.board_visibility.remove(&index),
+            new_visibility => self.board_visibility.insert(&index, &new_visibility),
+        };
+
+        SokobanEvent::BoardModerated(BoardModerated {
+            board_index: index,
+            moderator: &moderator,
+            action,
+            reason,
+        }).emit();
+
+        self.moderation_log.push(&ModerationRecord {
+            board_index: index,
+            moderator: moderator.clone(),
+            action,
+            reason,
+            timestamp: env::block_timestamp(),
+        });
+        self.internal_charge_storage(&moderator, initial_storage_usage, 0);
+    }
+
+    pub fn get_board_visibility(&self, index: BoardIndex) -> Option<BoardVisibility> {
+        self.boards.get(&index)?;
+        Some(self.board_visibility.get(&index).unwrap_or(BoardVisibility::Listed))
+    }
+
+    /// Listed boards with an index of at least `from_index`, in index order.
+    /// Listed boards from `from_index` on, at most `limit` of them. Only
+    /// `MAX_LISTING_SCAN` indices are looked at, so a page can have fewer boards
+    /// than `limit` and still be followed by another.
+    pub fn get_boards(&self, from_index: Option<BoardIndex>, limit: Option<u64>) -> BoardPage {
+        let limit = limit.unwrap_or(DEFAULT_LISTING_LIMIT) as usize;
+        let from_index = from_index.unwrap_or(0);
+        let end = std::cmp::min(self.next_board_index, from_index.saturating_add(MAX_LISTING_SCAN));
+
+        let mut boards = Vec::new();
+        let mut board_index = from_index;
+        while board_index < end && boards.len() < limit {
+            if !self.board_visibility.contains_key(&board_index) {
+                if let Some(board) = self.internal_get_board(board_index) {
+                    boards.push(BoardListing { board_index, board: board.into() });
+                }
+            }
+            board_index += 1;
+        }
+
+        BoardPage { boards, next_index: (board_index < self.next_board_index).then_some(board_index) }
+    }
+
+    pub fn get_moderation_log(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<ModerationRecord> {
+        let limit = limit.unwrap_or(DEFAULT_LISTING_LIMIT) as usize;
+
+        (from_index.unwrap_or(0)..self.moderation_log.len())
+            .take(limit)
+            .filter_map(|index| self.moderation_log.get(index))
+            .collect()
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_owner(&self) {
+        require!(env::predecessor_account_id() == self.owner_id, "Only the owner can call this method");
+    }
+
+    /// A board that can be opened and played: it exists and isn't hidden or deleted.
+    pub(crate) fn internal_get_accessible_board(&self, index: BoardIndex) -> Option<Board> {
+        match self.board_visibility.get(&index) {
+            Some(visibility) if !visibility.is_accessible() => None,
+            _ => self.internal_get_board(index),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, ONE_NEAR};
+    use near_contract_standards::storage_management::StorageManagement;
+
+    fn get_context(account: AccountId, deposit: u128) -> near_sdk::VMContext {
+        VMContextBuilder::new()
+            .predecessor_account_id(account)
+            .attached_deposit(deposit)
+            .block_timestamp(42)
+            .storage_usage(env::storage_usage())
+            .build()
+    }
+
+    fn setup() -> Contract {
+        testing_env!(get_context(accounts(0), 0));
+        let mut contract = Contract::new();
+        contract.add_moderator(accounts(1));
+
+        for account in [accounts(0), accounts(1), accounts(2)] {
+            testing_env!(get_context(account, ONE_NEAR));
+            contract.storage_deposit(None, None);
+        }
+
+        testing_env!(get_context(accounts(2), 0));
+        let mut board = Board::new(Size { width: 2, height: 1 });
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 1).unwrap();
+        for _ in 0..3 {
+            let index = contract.create_board(board.field.clone().into(), Some(Size { width: 2, height: 1 }), None);
+            contract.validate_board(index);
+        }
+
+        contract
+    }
+
+    #[test]
+    fn test_listing_respects_moderation() {
+        let mut contract = setup();
+
+        testing_env!(get_context(accounts(1), 0));
+        contract.moderate_board(0, ModerationAction::Unlist, ModerationReason::Duplicate);
+        contract.moderate_board(1, ModerationAction::Hide, ModerationReason::Offensive);
+
+        let listed: Vec<BoardIndex> = contract.get_boards(None, None).boards.iter().map(|l| l.board_index).collect();
+        assert_eq!(listed, vec![2]);
+
+        assert!(contract.get_board(0).is_some());
+        assert!(contract.get_board(1).is_none());
+        assert_eq!(contract.get_board_visibility(1), Some(BoardVisibility::Hidden));
+
+        contract.moderate_board(1, ModerationAction::Restore, ModerationReason::Other);
+        assert_eq!(contract.get_boards(None, Some(1)).boards[0].board_index, 1);
+
+        let log = contract.get_moderation_log(None, None);
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[1], ModerationRecord {
+            board_index: 1,
+            moderator: accounts(1),
+            action: ModerationAction::Hide,
+            reason: ModerationReason::Offensive,
+            timestamp: 42,
+        });
+    }
+
+    #[test]
+    fn test_moderator_pays_for_the_log() {
+        let mut contract = setup();
+        let used_bytes = contract.accounts_storage.get(&accounts(1)).unwrap().used_bytes;
+
+        testing_env!(get_context(accounts(1), 0));
+        contract.moderate_board(0, ModerationAction::Unlist, ModerationReason::Duplicate);
+        assert!(contract.accounts_storage.get(&accounts(1)).unwrap().used_bytes > used_bytes);
+    }
+
+    #[test]
+    #[should_panic(expected = "Account is not registered, call storage_deposit first")]
+    fn test_unregistered_moderator() {
+        let mut contract = setup();
+
+        testing_env!(get_context(accounts(0), 0));
+        contract.add_moderator(accounts(3));
+
+        testing_env!(get_context(accounts(3), 0));
+        contract.moderate_board(0, ModerationAction::Hide, ModerationReason::Offensive);
+    }
+
+    #[test]
+    fn test_listing_pages_by_board_index() {
+        let mut contract = setup();
+        contract.delete_board(0);
+
+        let page = contract.get_boards(Some(1), Some(1));
+        assert_eq!(page.boards.iter().map(|l| l.board_index).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(page.next_index, Some(2));
+        let page = contract.get_boards(page.next_index, None);
+        assert_eq!(page.boards.iter().map(|l| l.board_index).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(page.next_index, None);
+    }
+
+    #[test]
+    fn test_listing_scans_a_bounded_range() {
+        let mut contract = setup();
+        // Indices of deleted boards are looked at like any other.
+        contract.next_board_index = 3 + MAX_LISTING_SCAN + 1;
+
+        let page = contract.get_boards(Some(3), None);
+        assert!(page.boards.is_empty());
+        assert_eq!(page.next_index, Some(3 + MAX_LISTING_SCAN));
+        assert_eq!(contract.get_boards(page.next_index, None).next_index, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "No board")]
+    fn test_hidden_board_cant_be_played() {
+        let mut contract = setup();
+
+        testing_env!(get_context(accounts(0), 0));
+        contract.moderate_board(0, ModerationAction::Hide, ModerationReason::Broken);
+
+        testing_env!(get_context(accounts(2), 0));
+        contract.create_single_game(0, accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Board is deleted")]
+    fn test_deleted_board_cant_be_restored() {
+        let mut contract = setup();
+
+        testing_env!(get_context(accounts(1), 0));
+        contract.moderate_board(0, ModerationAction::Delete, ModerationReason::Spam);
+        contract.moderate_board(0, ModerationAction::Restore, ModerationReason::Other);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only moderators can moderate boards")]
+    fn test_moderate_by_other_account() {
+        let mut contract = setup();
+
+        testing_env!(get_context(accounts(0), 0));
+        contract.remove_moderator(accounts(1));
+
+        testing_env!(get_context(accounts(1), 0));
+        contract.moderate_board(0, ModerationAction::Hide, ModerationReason::Offensive);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn test_add_moderator_by_other_account() {
+        let mut contract = setup();
+
+        testing_env!(get_context(accounts(1), 0));
+        contract.add_moderator(accounts(3));
+    }
+}
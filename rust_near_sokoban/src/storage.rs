@@ -0,0 +1,320 @@
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{assert_one_yocto, env, near_bindgen, require, AccountId, Balance, Promise, StorageUsage};
+
+use crate::*;
+
+/// Storage paid by an account: its NEAR deposit and the bytes it currently occupies.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountStorage {
+    pub total: Balance,
+    pub used_bytes: StorageUsage,
+}
+
+/// Bytes written on behalf of `account_id` when a board or a game was created.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageCharge {
+    pub account_id: AccountId,
+    pub bytes: StorageUsage,
+}
+
+impl AccountStorage {
+    pub fn available(&self) -> Balance {
+        self.total.saturating_sub(Balance::from(self.used_bytes) * env::storage_byte_cost())
+    }
+
+    fn to_balance(&self) -> StorageBalance {
+        StorageBalance {
+            total: U128(self.total),
+            available: U128(self.available()),
+        }
+    }
+}
+
+impl Contract {
+    /// Measures how many bytes an account entry takes, the same way
+    /// `FungibleToken` does for its registration cost.
+    pub(crate) fn measure_account_storage_usage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = AccountId::new_unchecked("a".repeat(64));
+        self.accounts_storage.insert(&tmp_account_id, &AccountStorage { total: 0, used_bytes: 0 });
+        self.account_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.accounts_storage.remove(&tmp_account_id);
+    }
+
+    /// Bytes an `unclaimed` balance takes for the longest account ID.
+    pub(crate) fn measure_unclaimed_storage_usage(&mut self) -> StorageUsage {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = AccountId::new_unchecked("a".repeat(64));
+        self.unclaimed.insert(&tmp_account_id, &0);
+        let bytes = env::storage_usage() - initial_storage_usage;
+        self.unclaimed.remove(&tmp_account_id);
+        bytes
+    }
+
+    /// Charges the bytes written since `initial_storage_usage` to `account_id`, and
+    /// `reserved` more for what will be written later on its behalf.
+    /// Panics if the account's storage deposit doesn't cover them.
+    pub(crate) fn internal_charge_storage(
+        &mut self,
+        account_id: &AccountId,
+        initial_storage_usage: StorageUsage,
+        reserved: StorageUsage,
+    ) -> StorageUsage {
+        let bytes = env::storage_usage().saturating_sub(initial_storage_usage) + reserved;
+        let mut storage = self.accounts_storage
+            .get(account_id)
+            .expect("Account is not registered, call storage_deposit first");
+
+        storage.used_bytes += bytes;
+        require!(
+            Balance::from(storage.used_bytes) * env::storage_byte_cost() <= storage.total,
+            format!(
+                "Not enough storage deposit, {} yoctoNEAR required",
+                Balance::from(storage.used_bytes) * env::storage_byte_cost()
+            )
+        );
+
+        self.accounts_storage.insert(account_id, &storage);
+        bytes
+    }
+
+    /// Returns the bytes of a deleted board or game to the account that paid for them.
+    pub(crate) fn internal_release_storage(&mut self, charge: &StorageCharge) {
+        if let Some(mut storage) = self.accounts_storage.get(&charge.account_id) {
+            storage.used_bytes = storage.used_bytes.saturating_sub(charge.bytes);
+            self.accounts_storage.insert(&charge.account_id, &storage);
+        }
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let min_balance = self.storage_balance_bounds().min.0;
+
+        let storage = match self.accounts_storage.get(&account_id) {
+            Some(mut storage) => {
+                if registration_only == Some(true) {
+                    if amount > 0 {
+                        Promise::new(env::predecessor_account_id()).transfer(amount);
+                    }
+                } else {
+                    storage.total += amount;
+                }
+                storage
+            },
+            None => {
+                require!(amount >= min_balance, "The attached deposit is less than the minimum storage balance");
+
+                let total = if registration_only == Some(true) {
+                    let refund = amount - min_balance;
+                    if refund > 0 {
+                        Promise::new(env::predecessor_account_id()).transfer(refund);
+                    }
+                    min_balance
+                } else {
+                    amount
+                };
+
+                AccountStorage { total, used_bytes: self.account_storage_usage }
+            },
+        };
+
+        self.accounts_storage.insert(&account_id, &storage);
+        storage.to_balance()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut storage = self.accounts_storage
+            .get(&account_id)
+            .expect("Account is not registered");
+
+        let available = storage.available();
+        let amount = amount.map(|a| a.0).unwrap_or(available);
+        require!(amount <= available, "The amount is greater than the available storage balance");
+
+        if amount > 0 {
+            storage.total -= amount;
+            self.accounts_storage.insert(&account_id, &storage);
+            Promise::new(account_id).transfer(amount);
+        }
+
+        storage.to_balance()
+    }
+
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        require!(force != Some(true), "Force unregistration is not supported");
+
+        let account_id = env::predecessor_account_id();
+        match self.accounts_storage.get(&account_id) {
+            Some(storage) => {
+                require!(
+                    storage.used_bytes <= self.account_storage_usage,
+                    "Can't unregister the account while it owns boards or games"
+                );
+
+                self.accounts_storage.remove(&account_id);
+                Promise::new(account_id).transfer(storage.total);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(Balance::from(self.account_storage_usage) * env::storage_byte_cost()),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.accounts_storage.get(&account_id).map(|storage| storage.to_balance())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, ONE_NEAR};
+
+    fn get_context(account: AccountId, deposit: Balance) -> near_sdk::VMContext {
+        VMContextBuilder::new()
+            .predecessor_account_id(account)
+            .attached_deposit(deposit)
+            .storage_usage(env::storage_usage())
+            .build()
+    }
+
+    fn create_simple_board(contract: &mut Contract) -> BoardIndex {
+        let mut board = Board::new(Size { width: 2, height: 1 });
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 1).unwrap();
+        contract.create_board(board.field.into(), Some(Size { width: 2, height: 1 }), None)
+    }
+
+    #[test]
+    fn test_create_board_is_charged() {
+        testing_env!(get_context(accounts(0), 0));
+        let mut contract = Contract::new();
+
+        testing_env!(get_context(accounts(0), ONE_NEAR));
+        contract.storage_deposit(None, None);
+        let before = contract.storage_balance_of(accounts(0)).unwrap();
+
+        testing_env!(get_context(accounts(0), 0));
+        let index = create_simple_board(&mut contract);
+        let after = contract.storage_balance_of(accounts(0)).unwrap();
+
+        let charge = contract.board_charges.get(&index).unwrap();
+        assert!(charge.bytes > 0);
+        assert_eq!(
+            before.available.0 - after.available.0,
+            Balance::from(charge.bytes) * env::storage_byte_cost()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Account is not registered")]
+    fn test_create_board_unregistered() {
+        testing_env!(get_context(accounts(0), 0));
+        let mut contract = Contract::new();
+        create_simple_board(&mut contract);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough storage deposit")]
+    fn test_create_board_insufficient_deposit() {
+        testing_env!(get_context(accounts(0), 0));
+        let mut contract = Contract::new();
+
+        let min = contract.storage_balance_bounds().min.0;
+        testing_env!(get_context(accounts(0), min));
+        contract.storage_deposit(None, None);
+
+        testing_env!(get_context(accounts(0), 0));
+        create_simple_board(&mut contract);
+    }
+
+    #[test]
+    fn test_release_storage_refunds_available_balance() {
+        testing_env!(get_context(accounts(0), 0));
+        let mut contract = Contract::new();
+
+        testing_env!(get_context(accounts(0), ONE_NEAR));
+        contract.storage_deposit(None, None);
+        let before = contract.storage_balance_of(accounts(0)).unwrap();
+
+        testing_env!(get_context(accounts(0), 0));
+        let index = create_simple_board(&mut contract);
+        let charge = contract.board_charges.get(&index).unwrap();
+        contract.internal_release_storage(&charge);
+
+        let after = contract.storage_balance_of(accounts(0)).unwrap();
+        assert_eq!(before.available, after.available);
+    }
+
+    #[test]
+    fn test_game_charge_covers_its_history() {
+        testing_env!(get_context(accounts(0), 0));
+        let mut contract = Contract::new();
+
+        testing_env!(get_context(accounts(0), ONE_NEAR));
+        contract.storage_deposit(None, None);
+
+        testing_env!(get_context(accounts(0), 0));
+        let mut board = Board::new(Size { width: 4, height: 1 });
+        for (x, state) in [4, 1, 2, 6].into_iter().enumerate() {
+            board.set_state_at_cell(Point { x, y: 0 }, state).unwrap();
+        }
+        let index = contract.create_board(board.field.into(), Some(Size { width: 4, height: 1 }), None);
+        contract.validate_board(index);
+
+        let initial_storage_usage = env::storage_usage();
+        let game_index = contract.create_single_game(index, accounts(0));
+        contract.start_single_game(game_index);
+        for _ in 0..POSITION_HISTORY_LEN / 2 {
+            testing_env!(get_context(accounts(0), 0));
+            contract.step(game_index, Direction::Forward);
+            contract.step(game_index, Direction::Backward);
+        }
+
+        let charge = contract.game_charges.get(&game_index).unwrap();
+        assert_eq!(contract.get_single_game(game_index).unwrap().position_history.len(), POSITION_HISTORY_LEN);
+        assert!(env::storage_usage() - initial_storage_usage <= charge.bytes);
+    }
+
+    #[test]
+    fn test_withdraw_and_unregister() {
+        testing_env!(get_context(accounts(0), 0));
+        let mut contract = Contract::new();
+
+        testing_env!(get_context(accounts(0), ONE_NEAR));
+        contract.storage_deposit(None, None);
+
+        testing_env!(get_context(accounts(0), 1));
+        let balance = contract.storage_withdraw(Some(U128(ONE_NEAR / 2)));
+        assert_eq!(balance.total.0, ONE_NEAR / 2);
+
+        assert!(contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(accounts(0)).is_none());
+    }
+}
@@ -0,0 +1,69 @@
+use near_sdk::near_bindgen;
+
+pub use sokoban_core::svg::{SvgOptions, Theme};
+
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// The board as an SVG image, `Theme::Classic` by default.
+    pub fn get_board_svg(&self, index: BoardIndex, theme: Option<Theme>) -> Option<String> {
+        let board = self.internal_get_accessible_board(index)?;
+        Some(board.to_svg(&svg_options(theme)).or_panic())
+    }
+
+    /// The current position of a game as an SVG image.
+    pub fn get_game_svg(&self, index: GameIndex, theme: Option<Theme>) -> Option<String> {
+        let game = self.internal_get_game(index)?;
+        let layout = self.internal_get_board(game.board_index)?;
+        Some(layout.with_state(&game.state).to_svg(&svg_options(theme)).or_panic())
+    }
+}
+
+fn svg_options(theme: Option<Theme>) -> SvgOptions {
+    SvgOptions { theme: theme.unwrap_or_default(), ..Default::default() }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{env, testing_env, ONE_NEAR};
+    use near_contract_standards::storage_management::StorageManagement;
+
+    fn get_context(account: AccountId, deposit: u128) -> near_sdk::VMContext {
+        VMContextBuilder::new()
+            .predecessor_account_id(account)
+            .attached_deposit(deposit)
+            .storage_usage(env::storage_usage())
+            .build()
+    }
+
+    #[test]
+    fn test_game_svg_follows_the_game() {
+        testing_env!(get_context(accounts(0), 0));
+        let mut contract = Contract::new();
+
+        testing_env!(get_context(accounts(1), ONE_NEAR));
+        contract.storage_deposit(None, None);
+        testing_env!(get_context(accounts(1), 0));
+
+        let board = Board::from_xsb("@$ .").unwrap();
+        let index = contract.create_board(board.field.into(), Some(board.size), None);
+        let game_index = contract.create_single_game(index, accounts(1));
+        contract.start_single_game(game_index);
+
+        let before = contract.get_game_svg(game_index, None).unwrap();
+        assert_eq!(before, contract.get_board_svg(index, None).unwrap());
+
+        contract.step(game_index, Direction::Forward);
+        let after = contract.get_game_svg(game_index, None).unwrap();
+        assert_ne!(before, after);
+        assert!(after.contains("<circle cx='48' cy='16'"));
+
+        let dark = contract.get_board_svg(index, Some(Theme::Dark)).unwrap();
+        assert!(dark.contains(Theme::Dark.palette().floor));
+        assert!(contract.get_board_svg(index + 1, None).is_none());
+    }
+}
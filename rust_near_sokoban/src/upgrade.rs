@@ -0,0 +1,547 @@
+//! State versioning and code upgrades.
+//!
+//! Boards and games are stored as versioned records, so a record written by an
+//! older version of the contract can still be read after an upgrade. The state of
+//! the contract itself is tagged with its version under its own storage key, and
+//! `migrate` reads it through `VersionedContract` to convert it to the current layout.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::{env, near_bindgen, require, AccountId, Gas, Promise};
+
+use crate::*;
+
+/// A board as it is kept in storage. A new layout of `Board` gets a new variant,
+/// and the old ones are converted when they are read.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedBoard {
+    V1(Board),
+}
+
+impl From<VersionedBoard> for Board {
+    fn from(board: VersionedBoard) -> Self {
+        match board {
+            VersionedBoard::V1(board) => board,
+        }
+    }
+}
+
+impl From<Board> for VersionedBoard {
+    fn from(board: Board) -> Self {
+        VersionedBoard::V1(board)
+    }
+}
+
+/// A game as it is kept in storage, versioned like `VersionedBoard`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedGame {
+    V1(SingleplayerGame),
+}
+
+impl From<VersionedGame> for SingleplayerGame {
+    fn from(game: VersionedGame) -> Self {
+        match game {
+            VersionedGame::V1(game) => game,
+        }
+    }
+}
+
+impl From<SingleplayerGame> for VersionedGame {
+    fn from(game: SingleplayerGame) -> Self {
+        VersionedGame::V1(game)
+    }
+}
+
+/// A board of the first version: classic cells and rules, a single sokoban and plain boxes.
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct BoardV2 {
+    pub field: Vec<u8>,
+    pub is_valid: bool,
+    pub sokoban_position: Option<Point>,
+    pub size: Size,
+    pub field_len: usize,
+}
+
+impl From<BoardV2> for Board {
+    fn from(board: BoardV2) -> Self {
+        Self {
+            field: board.field,
+            is_valid: board.is_valid,
+            sokoban_position: board.sokoban_position,
+            size: board.size,
+            field_len: board.field_len,
+            encoding: CellEncoding::Classic,
+            ruleset: Ruleset::Classic,
+            teleporters: Vec::new(),
+            box_colors: Vec::new(),
+            goal_colors: Vec::new(),
+            one_way: Vec::new(),
+            gates: Vec::new(),
+            agents: 1,
+            topology: Topology::Bounded,
+            win_condition: WinCondition::BoxesOnGoals,
+        }
+    }
+}
+
+/// A game of the first version: it kept its own copy of the board and updated it on every move.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SingleplayerGameV1 {
+    pub board: BoardV2,
+    pub player: AccountId,
+    pub game_status: GameStatus,
+}
+
+/// State of the first deployed version of the contract.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub boards: Vector<BoardV2>,
+    pub games: Vector<SingleplayerGameV1>,
+}
+
+/// Storage key of the version of the contract state. The first version didn't write
+/// one, so its state is the one without it.
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+/// Version of the current layout of `Contract`.
+pub const STATE_VERSION: u8 = 2;
+
+/// The layouts of the contract state: the first deployed one and the current one.
+pub enum VersionedContract {
+    V1(ContractV1),
+    V2(Box<Contract>),
+}
+
+impl VersionedContract {
+    /// Reads the state in the layout its version says it was written in.
+    pub fn read() -> Self {
+        match env::storage_read(STATE_VERSION_KEY).as_deref() {
+            None => VersionedContract::V1(env::state_read().expect("The contract is not initialized")),
+            Some([STATE_VERSION]) => {
+                VersionedContract::V2(Box::new(env::state_read().expect("The contract is not initialized")))
+            },
+            Some(version) => panic!("Unknown state version {:?}", version),
+        }
+    }
+}
+
+/// Boards and games of the first version that `migrate_step` hasn't moved yet.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PendingMigration {
+    pub boards: Vector<BoardV2>,
+    pub games: Vector<SingleplayerGameV1>,
+    pub migrated_boards: u64,
+    pub migrated_games: u64,
+    /// Index of the first migrated board with a layout, by `layout_key`.
+    pub layouts: LookupMap<Vec<u8>, BoardIndex>,
+}
+
+/// Hash of the board without its pieces. A game is played on a board when the board
+/// with the game's position is the game's copy, which is when their keys are equal.
+fn layout_key(board: &Board) -> Vec<u8> {
+    let empty = BoardState {
+        sokoban: Point { x: 0, y: 0 },
+        boxes: Vec::new(),
+        hash: 0,
+        box_colors: Vec::new(),
+        agents: Vec::new(),
+    };
+    env::sha256(&board.with_state(&empty).try_to_vec().unwrap())
+}
+
+impl ContractV1 {
+    /// Starts the current layout with the boards and games of the first version left
+    /// to `migrate_step`. They keep their indices, so new ones are numbered after them.
+    pub fn migrate(self, owner_id: AccountId) -> Contract {
+        let mut contract = Contract {
+            owner_id,
+            boards: UnorderedMap::new(StorageKey::BoardsById),
+            games: LookupMap::new(StorageKey::GamesById),
+            escrows: LookupMap::new(StorageKey::Escrows),
+            unclaimed: LookupMap::new(StorageKey::Unclaimed),
+            fees_collected: 0,
+            accounts_storage: LookupMap::new(StorageKey::AccountsStorage),
+            account_storage_usage: 0,
+            board_charges: LookupMap::new(StorageKey::BoardCharges),
+            game_charges: LookupMap::new(StorageKey::GameCharges),
+            moderators: UnorderedSet::new(StorageKey::Moderators),
+            board_visibility: LookupMap::new(StorageKey::BoardVisibility),
+            moderation_log: Vector::new(StorageKey::ModerationLog),
+            next_board_index: self.boards.len(),
+            next_game_index: self.games.len(),
+            active_games: LookupMap::new(StorageKey::ActiveGames),
+            pending_migration: Some(PendingMigration {
+                boards: self.boards,
+                games: self.games,
+                migrated_boards: 0,
+                migrated_games: 0,
+                layouts: LookupMap::new(StorageKey::MigratedLayouts),
+            }),
+        };
+
+        contract.measure_account_storage_usage();
+        contract
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Converts the state written by the first version to the current layout.
+    /// The first version had no owner, so its state can only be migrated by the
+    /// contract account, which becomes the owner.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let predecessor = env::predecessor_account_id();
+
+        let contract = match VersionedContract::read() {
+            VersionedContract::V1(contract) => {
+                require!(predecessor == env::current_account_id(), "Only the contract account can migrate");
+                contract.migrate(predecessor)
+            },
+            VersionedContract::V2(contract) => {
+                require!(
+                    predecessor == contract.owner_id || predecessor == env::current_account_id(),
+                    "Only the owner can migrate"
+                );
+                *contract
+            },
+        };
+        Contract::internal_write_state_version();
+        contract
+    }
+
+    /// Moves up to `limit` records of the first version: its boards, then its games,
+    /// then it removes the moved ones. Returns whether the migration is complete.
+    /// Until it is, the boards and games that haven't been moved can't be found.
+    ///
+    /// Each record takes a read, a write and a hash of its board. Only host functions
+    /// are metered by the unit tests: 100 records of 64x64 boards take about 60 Tgas
+    /// of them, see `test_migrate_step_gas`, so a limit of 100 leaves room for wasm.
+    pub fn migrate_step(&mut self, limit: u64) -> bool {
+        require!(env::predecessor_account_id() == self.owner_id, "Only the owner can migrate");
+
+        let mut pending = match self.pending_migration.take() {
+            Some(pending) => pending,
+            None => return true,
+        };
+
+        for _ in 0..limit {
+            if !self.internal_migrate_record(&mut pending) {
+                return true;
+            }
+        }
+
+        self.pending_migration = Some(pending);
+        false
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+}
+
+impl Contract {
+    /// Moves the next record of the first version. A game is attached to the board with
+    /// the same layout; if there is none, its board is added as a new one. Returns false
+    /// when there is nothing left.
+    fn internal_migrate_record(&mut self, pending: &mut PendingMigration) -> bool {
+        if pending.migrated_boards < pending.boards.len() {
+            let index = pending.migrated_boards;
+            let board: Board = pending.boards.get(index).unwrap().into();
+            let key = layout_key(&board);
+            if !pending.layouts.contains_key(&key) {
+                pending.layouts.insert(&key, &index);
+            }
+
+            self.boards.insert(&index, &board.into());
+            pending.migrated_boards += 1;
+        } else if pending.migrated_games < pending.games.len() {
+            let index = pending.migrated_games;
+            let game = pending.games.get(index).unwrap();
+            let board: Board = game.board.into();
+            let key = layout_key(&board);
+            let board_index = match pending.layouts.get(&key) {
+                Some(board_index) => board_index,
+                None => {
+                    let board_index = self.next_board_index;
+                    self.next_board_index += 1;
+                    self.boards.insert(&board_index, &board.clone().into());
+                    pending.layouts.insert(&key, &board_index);
+                    board_index
+                },
+            };
+
+            let mut migrated = SingleplayerGame::from(board_index, &board, game.player);
+            migrated.game_status = game.game_status;
+            if migrated.game_status != GameStatus::Finished {
+                let active_games = self.active_games.get(&board_index).unwrap_or(0);
+                self.active_games.insert(&board_index, &(active_games + 1));
+            }
+            self.games.insert(&index, &migrated.into());
+            pending.migrated_games += 1;
+        } else if let Some(board) = pending.boards.pop() {
+            pending.layouts.remove(&layout_key(&board.into()));
+        } else if let Some(game) = pending.games.pop() {
+            pending.layouts.remove(&layout_key(&game.board.into()));
+        } else {
+            return false;
+        }
+        true
+    }
+
+    pub(crate) fn internal_write_state_version() {
+        env::storage_write(STATE_VERSION_KEY, &[STATE_VERSION]);
+    }
+
+    /// Deploys `code` to the contract account and calls `migrate` on it with the
+    /// remaining gas.
+    pub fn internal_upgrade(&self, code: Vec<u8>) -> Promise {
+        require!(env::predecessor_account_id() == self.owner_id, "Only the owner can upgrade");
+
+        let gas = env::prepaid_gas() - env::used_gas() - GAS_RESERVED_FOR_UPGRADE;
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call("migrate".to_string(), Vec::new(), 0, Gas(gas.0))
+    }
+}
+
+/// Takes the new contract code as raw input, so it doesn't have to be encoded as JSON.
+#[cfg(target_arch = "wasm32")]
+#[no_mangle]
+pub extern "C" fn upgrade() {
+    env::setup_panic_hook();
+    let contract: Contract = env::state_read().expect("The contract is not initialized");
+    let code = env::input().expect("Expected the new code as input");
+    contract.internal_upgrade(code);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn get_context(predecessor: AccountId) -> near_sdk::VMContext {
+        VMContextBuilder::new()
+            .current_account_id(accounts(5))
+            .predecessor_account_id(predecessor)
+            .storage_usage(env::storage_usage())
+            .build()
+    }
+
+    fn get_board() -> Board {
+        let mut board = Board::new(Size { width: 4, height: 1 });
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 6).unwrap();
+        board.validate_board()
+    }
+
+    fn to_v2(board: Board) -> BoardV2 {
+        BoardV2 {
+            field: board.field,
+            is_valid: board.is_valid,
+            sokoban_position: board.sokoban_position,
+            size: board.size,
+            field_len: board.field_len,
+        }
+    }
+
+    fn write_v1_state() {
+        let board = get_board();
+        let mut boards = Vector::new(StorageKey::Boards);
+        boards.push(&to_v2(Board::new(Size { width: 2, height: 2 })));
+        boards.push(&to_v2(board.clone()));
+
+        let mut games = Vector::new(StorageKey::Games);
+        games.push(&SingleplayerGameV1 {
+            board: to_v2(board.make_step(Direction::Forward).unwrap()),
+            player: accounts(0),
+            game_status: GameStatus::Running,
+        });
+
+        let mut other = Board::new(Size { width: 2, height: 1 });
+        other.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        other.set_state_at_cell(Point { x: 1, y: 0 }, 1).unwrap();
+        games.push(&SingleplayerGameV1 {
+            board: to_v2(other.validate_board()),
+            player: accounts(1),
+            game_status: GameStatus::Unactive,
+        });
+
+        env::state_write(&ContractV1 { boards, games });
+    }
+
+    #[test]
+    fn test_migrate_v1_state() {
+        testing_env!(get_context(accounts(5)));
+        write_v1_state();
+
+        let mut contract = Contract::migrate();
+        assert_eq!(env::storage_read(STATE_VERSION_KEY), Some(vec![STATE_VERSION]));
+        assert_eq!(contract.get_owner(), accounts(5));
+        assert!(contract.get_board(0).is_none());
+        assert!(contract.migrate_step(100));
+        assert!(contract.pending_migration.is_none());
+        assert_eq!(contract.boards.len(), 3);
+        assert_eq!(contract.next_board_index, 3);
+        assert_eq!(contract.next_game_index, 2);
+        assert_eq!(contract.active_games.get(&1), Some(1));
+
+        let game = contract.get_single_game(0).unwrap();
+        assert_eq!(game.board_index, 1);
+        assert_eq!(game.player, accounts(0));
+        assert_eq!(game.game_status, GameStatus::Running);
+        assert_eq!(game.state.sokoban, Point { x: 1, y: 0 });
+        assert_eq!(game.state.boxes, vec![Point { x: 2, y: 0 }]);
+        assert_eq!(contract.get_board(1).unwrap(), get_board().into());
+
+        let game = contract.get_single_game(1).unwrap();
+        assert_eq!(game.board_index, 2);
+        assert_eq!(contract.get_board(2).unwrap().size, Size { width: 2, height: 1 });
+    }
+
+    #[test]
+    fn test_migrated_state_is_playable() {
+        testing_env!(get_context(accounts(5)));
+        write_v1_state();
+
+        let mut contract = Contract::migrate();
+        contract.migrate_step(100);
+        env::state_write(&contract);
+
+        testing_env!(get_context(accounts(0)));
+        let mut contract: Contract = env::state_read().unwrap();
+        contract.step(0, Direction::Forward);
+        assert_eq!(contract.get_single_game(0).unwrap().game_status, GameStatus::Finished);
+    }
+
+    #[test]
+    fn test_migrate_v1_state_in_steps() {
+        testing_env!(get_context(accounts(5)));
+        write_v1_state();
+
+        let mut contract = Contract::migrate();
+        // Two boards and two games to move, then the same to remove.
+        for _ in 0..8 {
+            assert!(!contract.migrate_step(1));
+        }
+        assert!(contract.migrate_step(1));
+        assert!(contract.pending_migration.is_none());
+
+        assert_eq!(contract.next_board_index, 3);
+        assert_eq!(contract.get_single_game(0).unwrap().board_index, 1);
+        assert_eq!(contract.get_single_game(1).unwrap().board_index, 2);
+        assert_eq!(contract.active_games.get(&1), Some(1));
+        assert!(contract.migrate_step(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can migrate")]
+    fn test_migrate_step_by_other_account() {
+        testing_env!(get_context(accounts(5)));
+        write_v1_state();
+        let mut contract = Contract::migrate();
+
+        testing_env!(get_context(accounts(1)));
+        contract.migrate_step(100);
+    }
+
+    #[test]
+    fn test_owner_deletes_migrated_board() {
+        testing_env!(get_context(accounts(5)));
+        write_v1_state();
+        let mut contract = Contract::migrate();
+        contract.migrate_step(100);
+
+        contract.delete_board(0);
+        assert!(contract.get_board(0).is_none());
+    }
+
+    #[test]
+    fn test_migrate_step_gas() {
+        testing_env!(get_context(accounts(5)));
+        let size = Size { width: 64, height: 64 };
+        let mut board = Board::new(size);
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        let mut boards = Vector::new(StorageKey::Boards);
+        for _ in 0..100 {
+            boards.push(&to_v2(board.clone()));
+        }
+        env::state_write(&ContractV1 { boards, games: Vector::new(StorageKey::Games) });
+
+        let mut contract = Contract::migrate();
+        testing_env!(get_context(accounts(5)));
+        assert!(!contract.migrate_step(100));
+        println!("migrate_step(100) of 64x64 boards: {} Tgas", env::used_gas().0 / 1_000_000_000_000);
+        assert!(env::used_gas().0 < 100_000_000_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the contract account can migrate")]
+    fn test_migrate_v1_state_by_other_account() {
+        testing_env!(get_context(accounts(5)));
+        write_v1_state();
+
+        testing_env!(get_context(accounts(1)));
+        Contract::migrate();
+    }
+
+    #[test]
+    fn test_migrate_current_state_is_noop() {
+        testing_env!(get_context(accounts(1)));
+        env::state_write(&Contract::new());
+
+        let contract = Contract::migrate();
+        assert_eq!(contract.get_owner(), accounts(1));
+        assert!(contract.account_storage_usage > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown state version [7]")]
+    fn test_migrate_unknown_state_version() {
+        testing_env!(get_context(accounts(1)));
+        env::state_write(&Contract::new());
+        env::storage_write(STATE_VERSION_KEY, &[7]);
+
+        Contract::migrate();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can migrate")]
+    fn test_migrate_current_state_by_other_account() {
+        testing_env!(get_context(accounts(1)));
+        env::state_write(&Contract::new());
+
+        testing_env!(get_context(accounts(2)));
+        Contract::migrate();
+    }
+
+    #[test]
+    fn test_upgrade_deploys_and_migrates() {
+        testing_env!(get_context(accounts(1)));
+        let contract = Contract::new();
+        let _ = contract.internal_upgrade(vec![1, 2, 3]);
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, accounts(5));
+        assert!(matches!(&receipts[0].actions[0], VmAction::DeployContract { code } if code == &vec![1, 2, 3]));
+        assert!(matches!(
+            &receipts[0].actions[1],
+            VmAction::FunctionCall { function_name, .. } if function_name == "migrate"
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can upgrade")]
+    fn test_upgrade_by_other_account() {
+        testing_env!(get_context(accounts(1)));
+        let contract = Contract::new();
+
+        testing_env!(get_context(accounts(2)));
+        let _ = contract.internal_upgrade(vec![1, 2, 3]);
+    }
+}
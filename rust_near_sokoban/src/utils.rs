@@ -1,4 +1,27 @@
-use near_sdk::Balance;
+use near_sdk::{Balance, Gas, StorageUsage, Timestamp};
 
 pub const BID: Balance = 5 * 10u128.pow(24);
-pub const FEE: Balance = 1 * 10u128.pow(23);
\ No newline at end of file
+pub const FEE: Balance = 10u128.pow(23);
+
+// A wagered game has to be accepted by the player within a day
+pub const WAGER_ACCEPT_TIMEOUT: Timestamp = 24 * 60 * 60 * 10u64.pow(9);
+// and every following move has to be made within an hour of the previous one.
+pub const WAGER_MOVE_TIMEOUT: Timestamp = 60 * 60 * 10u64.pow(9);
+
+// A game without a wager can be deleted by its player after a week without moves.
+pub const GAME_EXPIRY_TIMEOUT: Timestamp = 7 * 24 * 60 * 60 * 10u64.pow(9);
+
+pub const GAS_FOR_ESCROW_CALLBACK: Gas = Gas(5_000_000_000_000);
+
+// Left to the upgrade call itself after scheduling `migrate` with the rest of the gas.
+pub const GAS_RESERVED_FOR_UPGRADE: Gas = Gas(10_000_000_000_000);
+
+// Upper bound on the moves applied by a single call of `steps`.
+pub const MAX_STEPS_PER_CALL: usize = 100;
+
+// Positions a game remembers to tell repeated ones, so its storage doesn't grow with every move.
+pub const POSITION_HISTORY_LEN: usize = 64;
+
+// A game starts with one position in its history and may grow to `POSITION_HISTORY_LEN`
+// hashes of 8 bytes, which is reserved when it is created.
+pub const GAME_HISTORY_RESERVE: StorageUsage = (POSITION_HISTORY_LEN as StorageUsage - 1) * 8;
@@ -0,0 +1,102 @@
+use std::fs;
+
+use sokoban_core::render::{ReplayOptions, Tileset};
+use sokoban_core::{Board, Direction, Heading};
+
+/// Image to write instead of starting a game.
+#[derive(PartialEq, Debug)]
+pub struct Export {
+    pub image: Image,
+    /// Sprite sheet to draw with, the built-in tiles otherwise.
+    pub sprites: Option<String>,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Image {
+    Png { path: String },
+    /// Replay of the moves in LURD notation.
+    Gif { path: String, lurd: String },
+}
+
+/// Export options that precede the board source, if any, and the rest of the arguments.
+pub fn parse_export_args(args: &[String]) -> Result<(Option<Export>, &[String]), String> {
+    let mut image = None;
+    let mut sprites = None;
+    let mut rest = args;
+
+    loop {
+        match rest {
+            [flag, path, tail @ ..] if flag == "--png" => {
+                image = Some(Image::Png { path: path.clone() });
+                rest = tail;
+            },
+            [flag, path, lurd, tail @ ..] if flag == "--gif" => {
+                image = Some(Image::Gif { path: path.clone(), lurd: lurd.clone() });
+                rest = tail;
+            },
+            [flag, path, tail @ ..] if flag == "--sprites" => {
+                sprites = Some(path.clone());
+                rest = tail;
+            },
+            _ => break,
+        }
+    }
+
+    match image {
+        Some(image) => Ok((Some(Export { image, sprites }), rest)),
+        None if sprites.is_some() => Err(String::from("--sprites needs --png or --gif")),
+        None => Ok((None, rest)),
+    }
+}
+
+/// Writes the image and returns its path.
+pub fn export(board: &Board, export: &Export) -> Result<String, String> {
+    let tiles = match &export.sprites {
+        Some(path) => {
+            let sheet = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            Tileset::from_sprite_sheet(&sheet).map_err(|e| e.to_string())?
+        },
+        None => Tileset::default(),
+    };
+
+    let (path, bytes) = match &export.image {
+        Image::Png { path } => (path, board.to_png(&tiles).map_err(|e| e.to_string())?),
+        Image::Gif { path, lurd } => {
+            let moves = lurd
+                .chars()
+                .map(|symbol| Direction::from_lurd(symbol).map(Heading::from))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            let gif = board.replay_to_gif(&moves, &tiles, ReplayOptions::default()).map_err(|e| e.to_string())?;
+            (path, gif)
+        },
+    };
+
+    fs::write(path, bytes).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(path.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_export_args() {
+        let all = args("--gif out.gif rRl --sprites tiles.png level.xsb");
+        let (export, rest) = parse_export_args(&all).unwrap();
+
+        assert_eq!(export, Some(Export {
+            image: Image::Gif { path: String::from("out.gif"), lurd: String::from("rRl") },
+            sprites: Some(String::from("tiles.png")),
+        }));
+        assert_eq!(rest, &all[5..]);
+
+        let all = args("--field AAAA --size 2x2");
+        assert_eq!(parse_export_args(&all).unwrap(), (None, &all[..]));
+        assert!(parse_export_args(&args("--sprites tiles.png level.xsb")).is_err());
+    }
+}
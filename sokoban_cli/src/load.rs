@@ -0,0 +1,253 @@
+use std::fs;
+
+use sokoban_core::{Board, CellEncoding, Direction, Point, Ruleset, Size, Topology, WinCondition};
+
+/// Where to read the board from, as given on the command line.
+pub enum Source {
+    Xsb(String),
+    Json(String),
+    Field { field: String, size: Size },
+}
+
+pub fn load_board(source: &Source) -> Result<Board, String> {
+    let board = match source {
+        Source::Xsb(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            Board::from_xsb(&text).map_err(|e| e.to_string())?
+        },
+        Source::Json(path) => {
+            let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            board_from_json(&text)?
+        },
+        Source::Field { field, size } => board_from_field(field, *size)?,
+    };
+
+    check_playable(board)
+}
+
+/// Boards the client can play: valid ones with a square grid, since the keys and
+/// LURD only have square moves.
+fn check_playable(board: Board) -> Result<Board, String> {
+    if !board.is_valid {
+        return Err(String::from("Invalid board to play!"));
+    }
+    if board.topology == Topology::Hex {
+        return Err(String::from("Hex boards can't be played in the terminal"));
+    }
+    Ok(board)
+}
+
+/// Base64 field and size, the way `create_board` accepts them.
+pub fn board_from_field(field: &str, size: Size) -> Result<Board, String> {
+    board_from_encoded_field(field, size, CellEncoding::Classic)
+}
+
+fn board_from_encoded_field(field: &str, size: Size, encoding: CellEncoding) -> Result<Board, String> {
+    let field = base64::decode(field.trim()).map_err(|e| e.to_string())?;
+    Board::from_encoded(field, size, encoding).map_err(|e| e.to_string())
+}
+
+/// JSON returned by the `get_board` view.
+pub fn board_from_json(text: &str) -> Result<Board, String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+
+    let field = value["field"].as_str().ok_or("Missing \"field\"")?;
+    let width = value["size"]["width"].as_u64().ok_or("Missing \"size.width\"")?;
+    let height = value["size"]["height"].as_u64().ok_or("Missing \"size.height\"")?;
+
+    let encoding = match value["encoding"].as_str() {
+        None | Some("Classic") => CellEncoding::Classic,
+        Some("Extended") => CellEncoding::Extended,
+        Some(other) => return Err(format!("Unknown encoding \"{}\"", other)),
+    };
+    let ruleset = match value["ruleset"].as_str() {
+        None | Some("Classic") => Ruleset::Classic,
+        Some("Ice") => Ruleset::Ice,
+        Some("Reverse") => Ruleset::Reverse,
+        Some(other) => return Err(format!("Unknown ruleset \"{}\"", other)),
+    };
+    let topology = match value["topology"].as_str() {
+        None | Some("Bounded") => Topology::Bounded,
+        Some("Toroidal") => Topology::Toroidal,
+        Some("Hex") => Topology::Hex,
+        Some(other) => return Err(format!("Unknown topology \"{}\"", other)),
+    };
+    let win_condition = match value["win_condition"].as_str() {
+        None | Some("BoxesOnGoals") => WinCondition::BoxesOnGoals,
+        Some("ReachExit") => WinCondition::ReachExit,
+        Some("BoxesOnGoalsAndExit") => WinCondition::BoxesOnGoalsAndExit,
+        Some(other) => return Err(format!("Unknown win condition \"{}\"", other)),
+    };
+
+    let teleporters = point_pairs_from_json(&value["teleporters"], "Invalid teleporter pair")?;
+    let box_colors = colors_from_json(&value["box_colors"])?;
+    let goal_colors = colors_from_json(&value["goal_colors"])?;
+    let gates = point_pairs_from_json(&value["gates"], "Invalid gate link")?;
+    let agents = match value["agents"].as_u64() {
+        None => 1,
+        Some(agents) => u8::try_from(agents).map_err(|_| "Invalid \"agents\"")?,
+    };
+
+    let mut one_way = Vec::new();
+    for entry in value["one_way"].as_array().into_iter().flatten() {
+        let cord = point_from_json(&entry[0]).ok_or("Invalid one-way entry")?;
+        let direction = match entry[1].as_str() {
+            Some("Backward") => Direction::Backward,
+            Some("Forward") => Direction::Forward,
+            Some("Up") => Direction::Up,
+            Some("Down") => Direction::Down,
+            _ => return Err(String::from("Invalid one-way entry")),
+        };
+        one_way.push((cord, direction));
+    }
+
+    let size = Size { width: width as usize, height: height as usize };
+    Ok(board_from_encoded_field(field, size, encoding)?
+        .with_ruleset(ruleset)
+        .with_teleporters(teleporters)
+        .with_colors(box_colors, goal_colors)
+        .with_one_way(one_way)
+        .with_gates(gates)
+        .with_agents(agents)
+        .with_topology(topology)
+        .with_win_condition(win_condition))
+}
+
+/// A list of `[point, point]` pairs, empty if missing.
+fn point_pairs_from_json(value: &serde_json::Value, error: &str) -> Result<Vec<(Point, Point)>, String> {
+    let mut pairs = Vec::new();
+    for pair in value.as_array().into_iter().flatten() {
+        let first = point_from_json(&pair[0]).ok_or(error)?;
+        let second = point_from_json(&pair[1]).ok_or(error)?;
+        pairs.push((first, second));
+    }
+    Ok(pairs)
+}
+
+/// A list of `[point, colour]` pairs, empty if missing.
+fn colors_from_json(value: &serde_json::Value) -> Result<Vec<(Point, u8)>, String> {
+    let mut colors = Vec::new();
+    for entry in value.as_array().into_iter().flatten() {
+        let cord = point_from_json(&entry[0]).ok_or("Invalid colour entry")?;
+        let color = entry[1].as_u64().and_then(|color| u8::try_from(color).ok()).ok_or("Invalid colour entry")?;
+        colors.push((cord, color));
+    }
+    Ok(colors)
+}
+
+fn point_from_json(value: &serde_json::Value) -> Option<Point> {
+    let x = value["x"].as_u64()?;
+    let y = value["y"].as_u64()?;
+    Some(Point { x: x as usize, y: y as usize })
+}
+
+/// Parses sizes written as `WIDTHxHEIGHT`.
+pub fn parse_size(text: &str) -> Result<Size, String> {
+    let (width, height) = text.split_once('x').ok_or("Size must look like 8x8")?;
+    let width = width.parse().map_err(|_| "Invalid width")?;
+    let height = height.parse().map_err(|_| "Invalid height")?;
+    Ok(Size { width, height })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_from_json() {
+        let board = Board::from_xsb("#@$.#").unwrap();
+        let json = format!(
+            "{{\"field\":\"{}\",\"is_valid\":true,\"sokoban_position\":{{\"x\":1,\"y\":0}},\
+             \"size\":{{\"width\":5,\"height\":1}},\"field_len\":3}}",
+            base64::encode(&board.field)
+        );
+
+        let loaded = board_from_json(&json).unwrap();
+        assert_eq!(loaded, board);
+        assert_eq!(loaded.sokoban_position, Some(Point { x: 1, y: 0 }));
+    }
+
+    #[test]
+    fn test_board_from_json_extended() {
+        let json = r#"{"field":"BAgG","size":{"width":3,"height":1},"encoding":"Extended","ruleset":"Ice"}"#;
+
+        let loaded = board_from_json(json).unwrap();
+        assert_eq!(loaded.encoding, CellEncoding::Extended);
+        assert_eq!(loaded.ruleset, Ruleset::Ice);
+        assert!(loaded.is_valid);
+    }
+
+    #[test]
+    fn test_board_from_json_teleporters() {
+        let json = r#"{"field":"BAIKBgo=","size":{"width":5,"height":1},"encoding":"Extended",
+            "teleporters":[[{"x":2,"y":0},{"x":4,"y":0}]]}"#;
+
+        let loaded = board_from_json(json).unwrap();
+        assert_eq!(loaded.teleporter_partner(Point { x: 4, y: 0 }), Some(Point { x: 2, y: 0 }));
+        assert!(loaded.is_valid);
+    }
+
+    #[test]
+    fn test_board_from_json_one_way_and_gates() {
+        // Sokoban, one-way, plate, gate.
+        let json = r#"{"field":"BA0QEw==","size":{"width":4,"height":1},"encoding":"Extended",
+            "one_way":[[{"x":1,"y":0},"Forward"]],"gates":[[{"x":2,"y":0},{"x":3,"y":0}]]}"#;
+
+        let loaded = board_from_json(json).unwrap();
+        assert_eq!(loaded.one_way_direction(Point { x: 1, y: 0 }), Some(Direction::Forward));
+        assert_eq!(loaded.gates, vec![(Point { x: 2, y: 0 }, Point { x: 3, y: 0 })]);
+        assert!(loaded.is_valid);
+    }
+
+    #[test]
+    fn test_board_from_json_win_condition() {
+        // Sokoban, floor, exit.
+        let json = r#"{"field":"BAEX","size":{"width":3,"height":1},"encoding":"Extended","win_condition":"ReachExit"}"#;
+
+        let loaded = board_from_json(json).unwrap();
+        assert_eq!(loaded.win_condition, WinCondition::ReachExit);
+        assert!(loaded.is_valid);
+        assert!(board_from_json(r#"{"field":"BAEX","size":{"width":3,"height":1},"win_condition":"Escape"}"#).is_err());
+    }
+
+    #[test]
+    fn test_board_from_json_colors() {
+        let board = Board::from_xsb("#@$.$.#").unwrap();
+        let json = format!(
+            "{{\"field\":\"{}\",\"size\":{{\"width\":7,\"height\":1}},\
+             \"box_colors\":[[{{\"x\":2,\"y\":0}},1]],\"goal_colors\":[[{{\"x\":5,\"y\":0}},1]]}}",
+            base64::encode(&board.field)
+        );
+
+        let loaded = board_from_json(&json).unwrap();
+        assert_eq!(loaded.box_color(Point { x: 2, y: 0 }), 1);
+        assert_eq!(loaded.goal_color(Point { x: 3, y: 0 }), 0);
+        assert!(loaded.is_valid);
+    }
+
+    #[test]
+    fn test_hex_board_is_not_playable() {
+        let board = Board::from_hex_xsb("# @ $ . #").unwrap();
+        assert!(board.is_valid);
+        assert!(check_playable(board.clone()).is_err());
+
+        let json = format!(
+            "{{\"field\":\"{}\",\"size\":{{\"width\":5,\"height\":1}},\"topology\":\"Hex\"}}",
+            base64::encode(&board.field)
+        );
+        assert_eq!(board_from_json(&json).unwrap().topology, Topology::Hex);
+        assert!(check_playable(Board::from_xsb("#@$.#").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_board_from_field_len_mismatch() {
+        let field = base64::encode([0u8; 3]);
+        assert!(board_from_field(&field, Size { width: 8, height: 8 }).is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4x2"), Ok(Size { width: 4, height: 2 }));
+        assert!(parse_size("4").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+//! Plays a board locally with the same rules as the contract.
+//!
+//! ```text
+//! sokoban-cli <level.xsb>
+//! sokoban-cli --json <get_board.json>
+//! sokoban-cli --field <base64> [--size 8x8]
+//! ```
+//!
+//! Any of these can be preceded by `--png <out.png>` or `--gif <out.gif> <lurd>` to
+//! write an image of the board or a replay of the moves instead of playing, and
+//! `--sprites <sheet.png>` to draw it with custom tiles.
+
+mod export;
+mod load;
+mod session;
+mod ui;
+
+use std::env;
+use std::process;
+
+use sokoban_core::Size;
+
+use crate::export::*;
+use crate::load::*;
+use crate::session::Session;
+
+const USAGE: &str = "usage: sokoban-cli [--png <out.png> | --gif <out.gif> <lurd>] [--sprites <sheet.png>] \
+    <level.xsb> | --json <board.json> | --field <base64> [--size WxH]";
+
+fn parse_args(args: &[String]) -> Result<Source, String> {
+    match args {
+        [flag, path] if flag == "--json" => Ok(Source::Json(path.clone())),
+        [flag, field] if flag == "--field" => Ok(Source::Field {
+            field: field.clone(),
+            size: Size { width: 8, height: 8 },
+        }),
+        [flag, field, size_flag, size] if flag == "--field" && size_flag == "--size" => Ok(Source::Field {
+            field: field.clone(),
+            size: parse_size(size)?,
+        }),
+        [path] if !path.starts_with("--") => Ok(Source::Xsb(path.clone())),
+        _ => Err(String::from(USAGE)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = parse_export_args(&args).and_then(|(export, rest)| {
+        let board = parse_args(rest).and_then(|source| load_board(&source))?;
+        match export {
+            Some(export) => self::export(&board, &export),
+            None => Session::new(board).and_then(|mut session| ui::play(&mut session).map_err(|e| e.to_string())),
+        }
+    });
+
+    match result {
+        Ok(lurd) => println!("{}", lurd),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        },
+    }
+}
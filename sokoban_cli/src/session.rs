@@ -0,0 +1,158 @@
+use sokoban_core::{Board, BoardState, Direction};
+
+/// A local playthrough of a board with undo and move/push counters.
+pub struct Session {
+    pub layout: Board,
+    pub initial: BoardState,
+    pub state: BoardState,
+    /// Sokoban moved by `step`, on boards with several.
+    pub agent: usize,
+    history: Vec<(BoardState, char)>,
+}
+
+impl Session {
+    pub fn new(layout: Board) -> Result<Self, String> {
+        let initial = layout.start_state().map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            state: initial.clone(),
+            initial,
+            layout,
+            agent: 0,
+            history: Vec::new(),
+        })
+    }
+
+    /// Selects the sokoban moved by `step`. Returns false if the board has no such sokoban.
+    pub fn select_agent(&mut self, agent: usize) -> bool {
+        if agent >= self.layout.agents as usize {
+            return false;
+        }
+        self.agent = agent;
+        true
+    }
+
+    /// Selects the sokoban after the current one, back to the first after the last.
+    pub fn next_agent(&mut self) {
+        self.agent = (self.agent + 1) % self.layout.agents.max(1) as usize;
+    }
+
+    /// Moves the selected sokoban. Returns false if the move isn't possible.
+    pub fn step(&mut self, direction: Direction) -> bool {
+        let previous = self.state.clone();
+        if !self.state.make_agent_step(&self.layout, self.agent, direction) {
+            return false;
+        }
+
+        let push = previous.boxes != self.state.boxes;
+        self.history.push((previous, direction.to_lurd(push)));
+        true
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some((state, _)) => {
+                self.state = state;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.state = self.initial.clone();
+        self.history.clear();
+    }
+
+    pub fn moves(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn pushes(&self) -> usize {
+        self.history.iter().filter(|(_, symbol)| symbol.is_ascii_uppercase()).count()
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.state.is_solved(&self.layout)
+    }
+
+    /// Moves made so far in LURD notation. It doesn't say which sokoban moved, so on
+    /// boards with several the moves of all of them are written in turn.
+    pub fn lurd(&self) -> String {
+        self.history.iter().map(|(_, symbol)| symbol).collect()
+    }
+
+    pub fn board(&self) -> Board {
+        self.layout.with_state(&self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sokoban_core::Point;
+
+    fn get_session() -> Session {
+        let board = Board::from_xsb("######\n#@ $.#\n#    #\n######").unwrap();
+        Session::new(board).unwrap()
+    }
+
+    #[test]
+    fn test_solve_and_export() {
+        let mut session = get_session();
+
+        assert!(session.step(Direction::Down));
+        assert!(session.step(Direction::Up));
+        assert!(session.step(Direction::Forward));
+        assert!(!session.is_solved());
+        assert!(session.step(Direction::Forward));
+
+        assert!(session.is_solved());
+        assert_eq!(session.moves(), 4);
+        assert_eq!(session.pushes(), 1);
+        assert_eq!(session.lurd(), "durR");
+    }
+
+    #[test]
+    fn test_blocked_move_is_not_recorded() {
+        let mut session = get_session();
+
+        assert!(!session.step(Direction::Up));
+        assert_eq!(session.moves(), 0);
+        assert_eq!(session.lurd(), "");
+    }
+
+    #[test]
+    fn test_undo_and_restart() {
+        let mut session = get_session();
+
+        session.step(Direction::Forward);
+        session.step(Direction::Forward);
+        assert!(session.undo());
+        assert_eq!(session.lurd(), "r");
+        assert_eq!(session.pushes(), 0);
+
+        session.step(Direction::Down);
+        session.restart();
+        assert_eq!(session.state, session.initial);
+        assert_eq!(session.moves(), 0);
+        assert!(!session.undo());
+    }
+
+    #[test]
+    fn test_select_agent() {
+        let board = Board::from_xsb("#@$. #\n#@$. #").unwrap().with_agents(2);
+        let mut session = Session::new(board).unwrap();
+
+        assert!(!session.select_agent(2));
+        assert!(session.select_agent(1));
+        assert!(session.step(Direction::Forward));
+        assert_eq!(session.state.agent_position(1), Some(Point { x: 2, y: 1 }));
+        assert_eq!(session.state.agent_position(0), Some(Point { x: 1, y: 0 }));
+
+        session.next_agent();
+        assert_eq!(session.agent, 0);
+        assert!(session.step(Direction::Forward));
+        assert!(session.is_solved());
+    }
+}
@@ -0,0 +1,88 @@
+use std::io::{self, Write};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, queue, terminal};
+use sokoban_core::Direction;
+
+use crate::session::Session;
+
+const HELP: &str = "arrows: move  u/backspace: undo  r: restart  e: export  q: quit";
+const AGENTS_HELP: &str = "tab/1-9: select sokoban";
+
+/// Runs the interactive loop until the player quits. Returns the LURD of the
+/// last position so it can be printed once the terminal is restored.
+pub fn play(session: &mut Session) -> io::Result<String> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    let result = run(session, &mut stdout);
+
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn run(session: &mut Session, stdout: &mut io::Stdout) -> io::Result<String> {
+    let mut message = String::new();
+
+    loop {
+        draw(session, stdout, &message)?;
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+
+        message.clear();
+        match key {
+            KeyEvent { code: KeyCode::Char('c'), modifiers: KeyModifiers::CONTROL, .. }
+            | KeyEvent { code: KeyCode::Char('q'), .. }
+            | KeyEvent { code: KeyCode::Esc, .. } => return Ok(session.lurd()),
+            KeyEvent { code: KeyCode::Left, .. } => { session.step(Direction::Backward); },
+            KeyEvent { code: KeyCode::Right, .. } => { session.step(Direction::Forward); },
+            KeyEvent { code: KeyCode::Up, .. } => { session.step(Direction::Up); },
+            KeyEvent { code: KeyCode::Down, .. } => { session.step(Direction::Down); },
+            KeyEvent { code: KeyCode::Char('u'), .. } | KeyEvent { code: KeyCode::Backspace, .. } => {
+                session.undo();
+            },
+            KeyEvent { code: KeyCode::Tab, .. } => session.next_agent(),
+            KeyEvent { code: KeyCode::Char(digit @ '1'..='9'), .. } => {
+                let agent = digit as usize - '1' as usize;
+                if !session.select_agent(agent) {
+                    message = format!("No sokoban {}", agent + 1);
+                }
+            },
+            KeyEvent { code: KeyCode::Char('r'), .. } => session.restart(),
+            KeyEvent { code: KeyCode::Char('e'), .. } => message = format!("LURD: {}", session.lurd()),
+            _ => (),
+        }
+    }
+}
+
+fn draw(session: &Session, stdout: &mut io::Stdout, message: &str) -> io::Result<()> {
+    queue!(stdout, terminal::Clear(terminal::ClearType::All), cursor::MoveTo(0, 0))?;
+
+    let xsb = session.board().to_xsb().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    for line in xsb.lines() {
+        write!(stdout, "{}\r\n", line)?;
+    }
+
+    write!(stdout, "\r\nMoves: {}  Pushes: {}", session.moves(), session.pushes())?;
+    if session.layout.agents > 1 {
+        write!(stdout, "  Sokoban: {}/{}", session.agent + 1, session.layout.agents)?;
+    }
+    write!(stdout, "\r\n")?;
+    if session.is_solved() {
+        write!(stdout, "Solved! LURD: {}\r\n", session.lurd())?;
+    }
+    if !message.is_empty() {
+        write!(stdout, "{}\r\n", message)?;
+    }
+    write!(stdout, "\r\n{}\r\n", HELP)?;
+    if session.layout.agents > 1 {
+        write!(stdout, "{}\r\n", AGENTS_HELP)?;
+    }
+
+    stdout.flush()
+}
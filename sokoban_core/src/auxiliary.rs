@@ -0,0 +1,141 @@
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Point {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Point {
+    pub fn get_point_in_direction(&self, direction: Direction) -> Option<Self> {
+        match direction {
+            Direction::Backward => {
+                if self.x != 0 {
+                    Some(Point {
+                            x: self.x - 1,
+                            ..*self
+                        })
+                } else { None }
+            },
+            Direction::Forward => Some(Point {
+                x: self.x + 1,
+                ..*self
+            }),
+            Direction::Up => {
+                if self.y != 0 {
+                    Some(Point {
+                        y: self.y - 1,
+                        ..*self
+                    })
+                } else { None }
+            }, 
+            Direction::Down => Some(Point {
+                y: self.y + 1,
+                ..*self
+            }),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Direction {
+    Backward,
+    Forward,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Backward => Direction::Forward,
+            Direction::Forward => Direction::Backward,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+}
+
+/// A move on a hex board, see `Topology::Hex`. Rows are flat, so there is no
+/// straight up or down.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HexDirection {
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+
+impl HexDirection {
+    pub fn opposite(self) -> Self {
+        match self {
+            HexDirection::East => HexDirection::West,
+            HexDirection::West => HexDirection::East,
+            HexDirection::NorthEast => HexDirection::SouthWest,
+            HexDirection::NorthWest => HexDirection::SouthEast,
+            HexDirection::SouthEast => HexDirection::NorthWest,
+            HexDirection::SouthWest => HexDirection::NorthEast,
+        }
+    }
+
+    /// Change of the axial coordinates `(x, y)` when moving this way.
+    pub fn axial_offset(self) -> (isize, isize) {
+        match self {
+            HexDirection::East => (1, 0),
+            HexDirection::West => (-1, 0),
+            HexDirection::NorthEast => (1, -1),
+            HexDirection::NorthWest => (0, -1),
+            HexDirection::SouthEast => (0, 1),
+            HexDirection::SouthWest => (-1, 1),
+        }
+    }
+}
+
+/// A move on either kind of board. Serialized as the direction it holds.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Heading {
+    Square(Direction),
+    Hex(HexDirection),
+}
+
+impl Heading {
+    pub fn opposite(self) -> Self {
+        match self {
+            Heading::Square(direction) => Heading::Square(direction.opposite()),
+            Heading::Hex(direction) => Heading::Hex(direction.opposite()),
+        }
+    }
+}
+
+impl From<Direction> for Heading {
+    fn from(direction: Direction) -> Self {
+        Heading::Square(direction)
+    }
+}
+
+impl From<HexDirection> for Heading {
+    fn from(direction: HexDirection) -> Self {
+        Heading::Hex(direction)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Size {
+    pub width: usize,
+    pub height: usize
+}
@@ -0,0 +1,1429 @@
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::auxiliary::*;
+use crate::cell::*;
+use crate::error::*;
+use crate::zobrist;
+
+/// Rules that change how pieces move on a board.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Ruleset {
+    #[default]
+    Classic,
+    /// A box pushed onto ice, or the sokoban walking onto it, keeps sliding in the
+    /// same direction until it hits a wall, a box or a cell without ice.
+    Ice,
+    /// Reverse Sokoban: the sokoban can't push, and a box next to it follows when it
+    /// moves away. Games start with the boxes on the goals and end when they are back
+    /// where the board has them, see `Board::start_state`.
+    Reverse,
+}
+
+/// What wins a game on a board.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WinCondition {
+    /// Every box stands on a goal of its colour.
+    #[default]
+    BoxesOnGoals,
+    /// A sokoban stands on an exit. Boxes and goals don't matter, so their numbers
+    /// don't have to match.
+    ReachExit,
+    /// A sokoban stands on an exit while every box stands on its goal.
+    BoxesOnGoalsAndExit,
+}
+
+impl WinCondition {
+    /// Whether the condition holds, given whether the boxes are placed and whether
+    /// a sokoban is on an exit.
+    pub fn is_met(self, boxes_placed: bool, on_exit: bool) -> bool {
+        match self {
+            WinCondition::BoxesOnGoals => boxes_placed,
+            WinCondition::ReachExit => on_exit,
+            WinCondition::BoxesOnGoalsAndExit => boxes_placed && on_exit,
+        }
+    }
+}
+
+/// How the edges of a board connect.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Topology {
+    /// Nothing lies beyond the edges of the field.
+    #[default]
+    Bounded,
+    /// Moving off an edge re-enters the field on the opposite edge.
+    Toroidal,
+    /// A grid of hexagons in axial coordinates: `x` grows to the east and `y` to
+    /// the south-east, so each cell has six neighbours, see `HexDirection`. Text
+    /// levels use the hex notation of `Board::from_hex_xsb`. One-way cells aren't
+    /// supported.
+    Hex,
+}
+
+/// Number of box and goal colours. Colour 0 is the plain colour every box and
+/// goal of a classic board has.
+pub const MAX_COLORS: u8 = 4;
+
+/// Field of a board, packed according to `encoding`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+pub struct Board {
+    pub field: Vec<u8>,
+    pub is_valid: bool,
+    /// Position of the first sokoban, see `agents`.
+    pub sokoban_position: Option<Point>, 
+    pub size: Size, 
+    pub field_len: usize,
+    pub encoding: CellEncoding,
+    pub ruleset: Ruleset,
+    /// Linked teleporter cells. Each teleporter is part of exactly one pair.
+    pub teleporters: Vec<(Point, Point)>,
+    /// Colours of the boxes that aren't plain, sorted by position. A box only
+    /// counts as placed on a goal of its own colour.
+    pub box_colors: Vec<(Point, u8)>,
+    /// Colours of the goals that aren't plain, sorted by position.
+    pub goal_colors: Vec<(Point, u8)>,
+    /// The direction each one-way cell can be crossed in.
+    pub one_way: Vec<(Point, Direction)>,
+    /// Pressure plates and the gates they open, as `(plate, gate)`. A gate opens
+    /// while a box rests on any plate linked to it.
+    pub gates: Vec<(Point, Point)>,
+    /// Number of sokobans the board is played with. Agents are numbered in
+    /// reading order, row by row, starting from 0.
+    pub agents: u8,
+    pub topology: Topology,
+    pub win_condition: WinCondition,
+}
+
+impl Board {
+    pub fn new(size: Size) -> Self {
+        Self::new_with_encoding(size, CellEncoding::Classic)
+    }
+
+    pub fn new_with_encoding(size: Size, encoding: CellEncoding) -> Self {
+        let field_len = encoding.field_len(size);
+
+        Self {
+            field: vec![0u8; field_len],
+            is_valid: false,
+            sokoban_position: None,
+            field_len,
+            size,
+            encoding,
+            ruleset: Ruleset::Classic,
+            teleporters: Vec::new(),
+            box_colors: Vec::new(),
+            goal_colors: Vec::new(),
+            one_way: Vec::new(),
+            gates: Vec::new(),
+            agents: 1,
+            topology: Topology::Bounded,
+            win_condition: WinCondition::BoxesOnGoals,
+        }
+    }
+
+    pub fn from(field: Vec<u8>, size: Size) -> Result<Self, SokobanError> {
+        Self::from_encoded(field, size, CellEncoding::Classic)
+    }
+
+    /// Reads a packed field. Every cell must be known and fit `encoding`. The board
+    /// follows the classic rules with one sokoban, has only plain boxes and goals,
+    /// and no teleporter pairs, one-way directions or gate links, see the `with_*`
+    /// methods.
+    pub fn from_encoded(field: Vec<u8>, size: Size, encoding: CellEncoding) -> Result<Self, SokobanError> {
+        let field_len = encoding.field_len(size);
+
+        if field.len() != field_len {
+            return Err(SokobanError::FieldLengthMismatch);
+        }
+
+        let board = Self {
+            field, 
+            is_valid: false,
+            sokoban_position: Option::None,
+            field_len,
+            size,
+            encoding,
+            ruleset: Ruleset::Classic,
+            teleporters: Vec::new(),
+            box_colors: Vec::new(),
+            goal_colors: Vec::new(),
+            one_way: Vec::new(),
+            gates: Vec::new(),
+            agents: 1,
+            topology: Topology::Bounded,
+            win_condition: WinCondition::BoxesOnGoals,
+        };
+
+        for index in 0..size.width * size.height {
+            Cell::try_from(encoding.read(&board.field, index))?;
+        }
+
+        Ok(board.validate_board())
+    }
+
+    /// The board played by `ruleset`, validated again.
+    pub fn with_ruleset(mut self, ruleset: Ruleset) -> Self {
+        self.ruleset = ruleset;
+        self.validate_board()
+    }
+
+    /// The board with its teleporters linked by `pairs`, validated again.
+    pub fn with_teleporters(mut self, pairs: Vec<(Point, Point)>) -> Self {
+        self.teleporters = pairs;
+        self.validate_board()
+    }
+
+    /// The board with the given box and goal colours, validated again. Plain
+    /// entries are dropped.
+    pub fn with_colors(mut self, box_colors: Vec<(Point, u8)>, goal_colors: Vec<(Point, u8)>) -> Self {
+        self.box_colors = sorted_colors(box_colors);
+        self.goal_colors = sorted_colors(goal_colors);
+        self.validate_board()
+    }
+
+    pub fn box_color(&self, cord: Point) -> u8 {
+        color_at(&self.box_colors, cord)
+    }
+
+    pub fn goal_color(&self, cord: Point) -> u8 {
+        color_at(&self.goal_colors, cord)
+    }
+
+    /// The board played with `agents` sokobans, validated again.
+    pub fn with_agents(mut self, agents: u8) -> Self {
+        self.agents = agents;
+        self.validate_board()
+    }
+
+    /// The board won by `win_condition`, validated again.
+    pub fn with_win_condition(mut self, win_condition: WinCondition) -> Self {
+        self.win_condition = win_condition;
+        self.validate_board()
+    }
+
+    /// The board laid out as `topology`, validated again.
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self.validate_board()
+    }
+
+    /// The board with the given one-way directions, validated again.
+    pub fn with_one_way(mut self, tiles: Vec<(Point, Direction)>) -> Self {
+        self.one_way = tiles;
+        self.validate_board()
+    }
+
+    /// The board with its plates linked to gates by `links`, validated again.
+    pub fn with_gates(mut self, links: Vec<(Point, Point)>) -> Self {
+        self.gates = links;
+        self.validate_board()
+    }
+
+    pub fn one_way_direction(&self, cord: Point) -> Option<Direction> {
+        self.one_way.iter().find(|(point, _)| *point == cord).map(|(_, direction)| *direction)
+    }
+
+    /// Whether a piece may leave `from` and enter `to` moving in `heading`:
+    /// one-way cells can only be entered and left in their own direction.
+    pub fn allows_move(&self, from: Point, to: Point, heading: impl Into<Heading>) -> bool {
+        let heading = heading.into();
+        [from, to]
+            .into_iter()
+            .all(|cord| self.one_way_direction(cord).is_none_or(|allowed| Heading::from(allowed) == heading))
+    }
+
+    /// The other end of the teleporter at `cord`.
+    pub fn teleporter_partner(&self, cord: Point) -> Option<Point> {
+        self.teleporters.iter().find_map(|&(first, second)| match cord {
+            _ if cord == first => Some(second),
+            _ if cord == second => Some(first),
+            _ => None,
+        })
+    }
+
+    pub fn get_cell(&self, cord: Point) -> Option<Cell> {
+        let state = self.get_state_at_cell(cord)?;
+        Some(Cell::try_from(state).expect("Fields only hold known cells"))
+    }
+
+    pub fn set_cell(&mut self, cord: Point, cell: Cell) -> Result<(), SokobanError> {
+        if !self.encoding.supports(cell) {
+            return Err(SokobanError::UnsupportedCell(cell));
+        }
+        if cord.x >= self.size.width || cord.y >= self.size.height {
+            return Err(SokobanError::OutOfField(cord));
+        }
+
+        let cell_index = cord.y * self.size.width + cord.x;
+        self.encoding.write(&mut self.field, cell_index, cell.into());
+        Ok(())
+    }
+
+    /// Raw state of the cell at `cord`, see `Cell` for the values.
+    pub fn get_state_at_cell(&self, cord: Point) -> Option<u8> {
+        if cord.x >= self.size.width || cord.y >= self.size.height {
+            return Option::None 
+        }
+
+        let cell_index = cord.y * self.size.width + cord.x;
+        Some(self.encoding.read(&self.field, cell_index))
+    }
+
+    pub fn set_state_at_cell(&mut self, cord: Point, state: u8) -> Result<(), SokobanError> {
+        self.set_cell(cord, Cell::try_from(state)?)
+    }
+
+    pub fn validate_board(&self) -> Self {
+        let mut board : Board = self.clone();
+
+        let mut sokoban_counter = 0;
+        // Boxes and goals of every colour.
+        let mut box_counters = [0; MAX_COLORS as usize];
+        let mut dest_counters = [0; MAX_COLORS as usize];
+
+        let mut has_ice = false;
+        let mut has_exit = false;
+        let mut teleporters_linked = true;
+        let mut tiles_configured = true;
+
+        for x in 0..self.size.width {
+            for y in 0..self.size.height {
+                let cell = board.get_cell(Point { x, y }).unwrap();
+                has_ice |= cell.is_ice();
+                has_exit |= cell.is_exit();
+
+                if cell.is_teleporter() {
+                    let links = self.teleporters
+                        .iter()
+                        .filter(|(first, second)| *first == Point { x, y } || *second == Point { x, y })
+                        .count();
+                    teleporters_linked &= links == 1;
+                }
+                if cell.is_one_way() {
+                    tiles_configured &= self.one_way.iter().filter(|(cord, _)| *cord == Point { x, y }).count() == 1;
+                }
+                if cell.is_gate() {
+                    tiles_configured &= self.gates.iter().any(|(_, gate)| *gate == Point { x, y });
+                }
+
+                if cell.has_sokoban() {
+                    sokoban_counter += 1;
+                }
+                if cell.has_box() {
+                    let color = self.box_color(Point { x, y }).min(MAX_COLORS - 1);
+                    box_counters[color as usize] += 1;
+                }
+                if cell.is_goal() {
+                    let color = self.goal_color(Point { x, y }).min(MAX_COLORS - 1);
+                    dest_counters[color as usize] += 1;
+                }
+            }
+        }
+
+        // Every teleporter has exactly one partner, and pairs only link teleporters.
+        teleporters_linked &= self.teleporters.iter().all(|&(first, second)| {
+            first != second
+                && self.get_cell(first).is_some_and(Cell::is_teleporter)
+                && self.get_cell(second).is_some_and(Cell::is_teleporter)
+        });
+
+        // Directions only belong to one-way cells, and links go from a plate to a gate.
+        tiles_configured &= self.one_way.iter().all(|(cord, _)| self.get_cell(*cord).is_some_and(Cell::is_one_way))
+            && self.gates.iter().all(|&(plate, gate)| {
+                self.get_cell(plate).is_some_and(Cell::is_plate) && self.get_cell(gate).is_some_and(Cell::is_gate)
+            });
+
+        // Colours only belong to boxes and goals, and stay below `MAX_COLORS`.
+        let colors_placed = self.box_colors.iter().all(|&(cord, color)| {
+            color < MAX_COLORS && self.get_cell(cord).is_some_and(Cell::has_box)
+        }) && self.goal_colors.iter().all(|&(cord, color)| {
+            color < MAX_COLORS && self.get_cell(cord).is_some_and(Cell::is_goal)
+        });
+
+        let is_valid = sokoban_counter == self.agents as usize
+            && self.agents > 0
+            && (box_counters == dest_counters || self.win_condition == WinCondition::ReachExit)
+            && (has_exit || self.win_condition == WinCondition::BoxesOnGoals)
+            && colors_placed
+            && (!has_ice || self.ruleset == Ruleset::Ice)
+            && (self.ruleset != Ruleset::Reverse || !self.sokobans().into_iter().any(|cord| self.is_goal(cord)))
+            && teleporters_linked
+            && tiles_configured
+            && (self.topology != Topology::Hex || self.one_way.is_empty());
+        board.is_valid = is_valid;
+
+        if is_valid {
+            board.sokoban_position = self.sokobans().first().copied();
+        }
+
+        board
+    }
+
+    pub fn make_step(&self, direction: Direction) -> Result<Self, SokobanError> {
+        self.make_agent_step(0, direction)
+    }
+
+    /// Moves the sokoban of `agent`. Agents the board doesn't have stay put.
+    pub fn make_agent_step(&self, agent: usize, direction: Direction) -> Result<Self, SokobanError> {
+        let mut state = self.get_state()?;
+        state.make_agent_step(self, agent, direction);
+        Ok(self.with_state(&state))
+    }
+
+    /// Cells holding a sokoban, in reading order.
+    fn sokobans(&self) -> Vec<Point> {
+        let mut sokobans = Vec::new();
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                if self.get_cell(Point { x, y }).is_some_and(Cell::has_sokoban) {
+                    sokobans.push(Point { x, y });
+                }
+            }
+        }
+        sokobans
+    }
+
+    /// The cell next to `cord` in `heading`, if the field has one. On a toroidal
+    /// board the cells on opposite edges are next to each other. Square directions
+    /// lead nowhere on a hex board and hex directions nowhere on a square one.
+    pub fn neighbor(&self, cord: Point, heading: impl Into<Heading>) -> Option<Point> {
+        let Size { width, height } = self.size;
+        let next = match (self.topology, heading.into()) {
+            (Topology::Bounded, Heading::Square(direction)) => cord.get_point_in_direction(direction)?,
+            (Topology::Toroidal, Heading::Square(Direction::Backward)) => Point { x: (cord.x + width - 1) % width, ..cord },
+            (Topology::Toroidal, Heading::Square(Direction::Forward)) => Point { x: (cord.x + 1) % width, ..cord },
+            (Topology::Toroidal, Heading::Square(Direction::Up)) => Point { y: (cord.y + height - 1) % height, ..cord },
+            (Topology::Toroidal, Heading::Square(Direction::Down)) => Point { y: (cord.y + 1) % height, ..cord },
+            (Topology::Hex, Heading::Hex(direction)) => {
+                let (dx, dy) = direction.axial_offset();
+                Point { x: cord.x.checked_add_signed(dx)?, y: cord.y.checked_add_signed(dy)? }
+            },
+            _ => return None,
+        };
+        (next.x < width && next.y < height).then_some(next)
+    }
+
+    /// Where `cord` is drawn, in half cells from the left and in cells from the top.
+    /// Hex boards are drawn the way `to_hex_xsb` writes them: odd rows are shifted by
+    /// half a cell and the walls the level doesn't reach are left out, as `None`.
+    pub fn drawn_at(&self, cord: Point) -> Option<(usize, usize)> {
+        if self.topology != Topology::Hex {
+            return Some((2 * cord.x, cord.y));
+        }
+
+        let (shift, columns) = self.hex_columns();
+        let first = shift - cord.y / 2;
+        (first..first + columns).contains(&cord.x).then(|| (2 * (cord.x - first) + cord.y % 2, cord.y))
+    }
+
+    /// Width in half cells and height in cells of the drawing, see `drawn_at`.
+    pub fn drawn_size(&self) -> (usize, usize) {
+        match self.topology {
+            Topology::Hex => {
+                let (_, columns) = self.hex_columns();
+                let shifted = usize::from(self.size.height > 1);
+                (2 * columns + shifted, self.size.height)
+            },
+            _ => (2 * self.size.width, self.size.height),
+        }
+    }
+
+    /// Column of the first cell of the top row of a hex level, and its cells per row.
+    pub(crate) fn hex_columns(&self) -> (usize, usize) {
+        let shift = self.size.height.saturating_sub(1) / 2;
+        (shift, self.size.width.saturating_sub(shift))
+    }
+
+    /// Whether `heading` is a move on the grid of this board: hex directions on hex
+    /// boards, square ones on the others.
+    pub fn accepts(&self, heading: Heading) -> bool {
+        matches!(heading, Heading::Hex(_)) == (self.topology == Topology::Hex)
+    }
+
+    /// Walls and cells beyond the field can't be entered by the sokoban or a box.
+    pub fn is_wall(&self, cord: Point) -> bool {
+        self.get_cell(cord).is_none_or(Cell::is_wall)
+    }
+
+    pub fn is_goal(&self, cord: Point) -> bool {
+        self.get_cell(cord).is_some_and(Cell::is_goal)
+    }
+
+    /// Ice only makes pieces slide on boards with `Ruleset::Ice`.
+    pub fn is_slippery(&self, cord: Point) -> bool {
+        self.ruleset == Ruleset::Ice && self.get_cell(cord).is_some_and(Cell::is_ice)
+    }
+
+    /// Extracts the dynamic part of a valid board: the sokobans and the boxes.
+    pub fn get_state(&self) -> Result<BoardState, SokobanError> {
+        let sokoban = self.sokoban_position.ok_or(SokobanError::InvalidBoard)?;
+        let agents: Vec<Point> = self.sokobans().into_iter().filter(|cord| *cord != sokoban).collect();
+        let mut boxes = Vec::new();
+
+        for x in 0..self.size.width {
+            for y in 0..self.size.height {
+                if self.get_cell(Point { x, y }).is_some_and(Cell::has_box) {
+                    boxes.push(Point { x, y });
+                }
+            }
+        }
+
+        let box_colors = self.box_colors.clone();
+        let hash = zobrist::hash_position(sokoban, &boxes)
+            ^ zobrist::hash_colors(&box_colors)
+            ^ zobrist::hash_agents(&agents);
+        Ok(BoardState { sokoban, boxes, hash, box_colors, agents })
+    }
+
+    /// The position games on this board start from. Under `Ruleset::Reverse` that is
+    /// the solved one: every box stands on a goal, coloured like it. Otherwise it is
+    /// the position of the board.
+    pub fn start_state(&self) -> Result<BoardState, SokobanError> {
+        let mut state = self.get_state()?;
+        if self.ruleset != Ruleset::Reverse {
+            return Ok(state);
+        }
+
+        state.boxes.clear();
+        for x in 0..self.size.width {
+            for y in 0..self.size.height {
+                if self.is_goal(Point { x, y }) {
+                    state.boxes.push(Point { x, y });
+                }
+            }
+        }
+        state.box_colors = self.goal_colors.clone();
+        state.hash = zobrist::hash_position(state.sokoban, &state.boxes)
+            ^ zobrist::hash_colors(&state.box_colors)
+            ^ zobrist::hash_agents(&state.agents);
+        Ok(state)
+    }
+
+    /// Renders `state` over the static layout of the board. Gates are drawn open or
+    /// closed for that position.
+    pub fn with_state(&self, state: &BoardState) -> Self {
+        let mut board: Board = self.clone();
+
+        for x in 0..self.size.width {
+            for y in 0..self.size.height {
+                let cord = Point { x, y };
+                let cell = state.cell_at(self, cord).expect("Cell is inside the field");
+                board.set_cell(cord, cell).expect("Cell is inside the field");
+            }
+        }
+
+        board.sokoban_position = Some(state.sokoban);
+        board.box_colors = state.box_colors.clone();
+        board
+    }
+
+    pub fn state_as_symbol(state: u8) -> Result<char, SokobanError> {
+        let symbol = match Cell::try_from(state)? {
+            Cell::Wall => '*',
+            Cell::Floor => '.',
+            Cell::Box => 'c',
+            Cell::BoxOnGoal => 'C',
+            Cell::Sokoban => 's',
+            Cell::SokobanOnGoal => 'S',
+            Cell::Goal => 'X',
+            Cell::Ice => '~',
+            Cell::BoxOnIce => 'i',
+            Cell::SokobanOnIce => 'I',
+            Cell::Teleporter => 'T',
+            Cell::BoxOnTeleporter => 'q',
+            Cell::SokobanOnTeleporter => 'Q',
+            Cell::OneWay => 'w',
+            Cell::BoxOnOneWay => 'y',
+            Cell::SokobanOnOneWay => 'Y',
+            Cell::Plate => 'p',
+            Cell::BoxOnPlate => 'b',
+            Cell::SokobanOnPlate => 'P',
+            Cell::Gate => 'G',
+            Cell::OpenGate => 'g',
+            Cell::BoxOnGate => 'h',
+            Cell::SokobanOnGate => 'H',
+            Cell::Exit => 'E',
+            Cell::BoxOnExit => 'z',
+            Cell::SokobanOnExit => 'Z',
+        };
+        Ok(symbol)
+    }
+
+    pub fn get_board_as_strings(&self) -> Result<Vec<String>, SokobanError> {
+        let mut vector = Vec::new();
+
+        for i in 0..self.size.height {
+            let mut result = String::from("");
+            for j in 0..self.size.width {
+                let unwraped_cell = self.get_state_at_cell(Point { x: j, y: i }).unwrap();
+                let symbol = Self::state_as_symbol(unwraped_cell)?;
+                result.push(symbol);
+            }
+            vector.push(result);
+        }
+
+        Ok(vector)
+    }
+}
+
+/// Positions that change during a game. Walls and goals never move, so they are
+/// read from the `Board` the game was created from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BoardState {
+    /// Position of the first sokoban, agent 0.
+    pub sokoban: Point,
+    /// Box positions, kept sorted.
+    pub boxes: Vec<Point>,
+    /// Zobrist hash of the position, see `zobrist::hash_position`.
+    pub hash: u64,
+    /// Colours of the boxes that aren't plain, sorted by position.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub box_colors: Vec<(Point, u8)>,
+    /// Positions of the other sokobans, agent 1 onwards. Empty on boards with one.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub agents: Vec<Point>,
+}
+
+impl BoardState {
+    pub fn has_box(&self, cord: Point) -> bool {
+        self.boxes.binary_search(&cord).is_ok()
+    }
+
+    pub fn box_color(&self, cord: Point) -> u8 {
+        color_at(&self.box_colors, cord)
+    }
+
+    /// Position of the sokoban of `agent`.
+    pub fn agent_position(&self, agent: usize) -> Option<Point> {
+        match agent {
+            0 => Some(self.sokoban),
+            _ => self.agents.get(agent - 1).copied(),
+        }
+    }
+
+    fn set_agent_position(&mut self, agent: usize, cord: Point) {
+        match agent {
+            0 => self.sokoban = cord,
+            _ => self.agents[agent - 1] = cord,
+        }
+    }
+
+    pub fn has_sokoban(&self, cord: Point) -> bool {
+        self.sokoban == cord || self.agents.contains(&cord)
+    }
+
+    /// Neither a box nor a sokoban stands on `cord`.
+    fn is_free(&self, cord: Point) -> bool {
+        !self.has_box(cord) && !self.has_sokoban(cord)
+    }
+
+    /// Moves the first sokoban, see `make_agent_step`.
+    pub fn make_step(&mut self, layout: &Board, direction: Direction) -> bool {
+        self.make_agent_step(layout, 0, direction)
+    }
+
+    /// Moves the sokoban of `agent` on a square board, see `make_move`.
+    pub fn make_agent_step(&mut self, layout: &Board, agent: usize, direction: Direction) -> bool {
+        self.make_move(layout, agent, direction.into())
+    }
+
+    /// Moves the sokoban of `agent` on a hex board, see `make_move`.
+    pub fn make_hex_step(&mut self, layout: &Board, agent: usize, direction: HexDirection) -> bool {
+        self.make_move(layout, agent, direction.into())
+    }
+
+    /// Moves the sokoban of `agent` according to the rules of `layout`, pushing a
+    /// box if there is one in the way, or pulling the one behind it under
+    /// `Ruleset::Reverse`. Sokobans block each other like walls. A box
+    /// or sokoban that stops on a teleporter comes out of its partner when that
+    /// cell is free. Closed gates block like walls and one-way cells only let
+    /// pieces through in their direction. Gates are evaluated again from the new
+    /// position. Returns false if the move isn't possible, including a `heading`
+    /// that doesn't fit the grid of `layout`.
+    pub fn make_move(&mut self, layout: &Board, agent: usize, heading: Heading) -> bool {
+        let Some(start) = self.agent_position(agent) else {
+            return false;
+        };
+        let next_cell = match layout.neighbor(start, heading) {
+            Some(cell) if self.can_enter(layout, start, cell, heading) && !self.has_sokoban(cell) => cell,
+            _ => return false,
+        };
+
+        let is_pull = layout.ruleset == Ruleset::Reverse;
+        let after_next_cell = match layout.neighbor(next_cell, heading) {
+            _ if !self.has_box(next_cell) => None,
+            Some(cell) if !is_pull && self.can_enter(layout, next_cell, cell, heading) && self.is_free(cell) => Some(cell),
+            _ => return false,
+        };
+        // Checked before the sokoban leaves, while a gate it stands in is still open.
+        let pulled = layout
+            .neighbor(start, heading.opposite())
+            .filter(|behind| is_pull && self.has_box(*behind) && self.can_enter(layout, *behind, start, heading));
+
+        // The sokoban stands on `next_cell` from here on, so nothing else ends up there.
+        self.set_agent_position(agent, next_cell);
+
+        if let Some(after_next_cell) = after_next_cell {
+            let box_end = self.slide(layout, after_next_cell, heading);
+            let box_end = self.exit(layout, box_end).unwrap_or(box_end);
+            self.move_box(next_cell, box_end);
+        }
+        if let Some(behind) = pulled {
+            let box_end = self.exit(layout, start).unwrap_or(start);
+            self.move_box(behind, box_end);
+        }
+
+        let sokoban_end = self.slide(layout, next_cell, heading);
+        let sokoban_end = self.exit(layout, sokoban_end).unwrap_or(sokoban_end);
+        self.hash ^= zobrist::agent_key(start, agent) ^ zobrist::agent_key(sokoban_end, agent);
+        self.set_agent_position(agent, sokoban_end);
+        true
+    }
+
+    /// Moves the box at `from` and its colour to `to`, updating the hash.
+    fn move_box(&mut self, from: Point, to: Point) {
+        let box_index = self.boxes.binary_search(&from).expect("A box stands on the cell");
+        self.boxes.remove(box_index);
+        let insert_index = self.boxes.binary_search(&to).unwrap_err();
+        self.boxes.insert(insert_index, to);
+        self.hash ^= zobrist::box_key(from) ^ zobrist::box_key(to);
+
+        if let Ok(color_index) = self.box_colors.binary_search_by_key(&from, |(cord, _)| *cord) {
+            let (_, color) = self.box_colors.remove(color_index);
+            let insert_index = self.box_colors.binary_search_by_key(&to, |(cord, _)| *cord).unwrap_err();
+            self.box_colors.insert(insert_index, (to, color));
+            self.hash ^= zobrist::color_key(from, color) ^ zobrist::color_key(to, color);
+        }
+    }
+
+    /// Where a piece that entered `cord` moving in `heading` stops: it keeps sliding
+    /// over slippery cells while the next cell is free.
+    fn slide(&self, layout: &Board, mut cord: Point, heading: Heading) -> Point {
+        while layout.is_slippery(cord) {
+            match layout.neighbor(cord, heading) {
+                Some(next) if self.can_enter(layout, cord, next, heading) && self.is_free(next) => cord = next,
+                _ => break,
+            }
+        }
+        cord
+    }
+
+    /// Whether a piece at `from` can move to `to`, leaving boxes aside.
+    fn can_enter(&self, layout: &Board, from: Point, to: Point, heading: Heading) -> bool {
+        let closed = layout.get_cell(to).is_some_and(Cell::is_gate) && !self.is_gate_open(layout, to);
+        !layout.is_wall(to) && !closed && layout.allows_move(from, to, heading)
+    }
+
+    /// A gate is open while a box rests on one of its plates, and can't close on
+    /// a box or a sokoban standing in it.
+    pub fn is_gate_open(&self, layout: &Board, gate: Point) -> bool {
+        !self.is_free(gate)
+            || layout.gates.iter().any(|&(plate, linked)| linked == gate && self.has_box(plate))
+    }
+
+    /// The partner of the teleporter at `cord`, if nothing stands on it.
+    fn exit(&self, layout: &Board, cord: Point) -> Option<Point> {
+        layout.teleporter_partner(cord).filter(|exit| self.is_free(*exit))
+    }
+
+    /// Cells that differ between `previous` and this position: where the sokobans
+    /// were and are, and the boxes that moved.
+    pub fn moved_points(&self, previous: &BoardState) -> Vec<Point> {
+        let mut points = vec![previous.sokoban, self.sokoban];
+        points.extend(previous.agents.iter().chain(&self.agents));
+        points.extend(previous.boxes.iter().filter(|cord| !self.has_box(**cord)));
+        points.extend(self.boxes.iter().filter(|cord| !previous.has_box(**cord)));
+        points
+    }
+
+    /// The cell at `cord` with this position rendered over `layout`.
+    pub fn cell_at(&self, layout: &Board, cord: Point) -> Option<Cell> {
+        let terrain = match layout.get_cell(cord)?.terrain() {
+            Cell::Gate | Cell::OpenGate if self.is_gate_open(layout, cord) => Cell::OpenGate,
+            Cell::Gate | Cell::OpenGate => Cell::Gate,
+            terrain => terrain,
+        };
+        let cell = match terrain {
+            Cell::Wall => Cell::Wall,
+            _ if self.has_sokoban(cord) => terrain.with_sokoban(),
+            _ if self.has_box(cord) => terrain.with_box(),
+            _ => terrain,
+        };
+        Some(cell)
+    }
+
+    /// State of the cell at `cord` with this position rendered over `layout`.
+    pub fn state_at(&self, layout: &Board, cord: Point) -> Option<u8> {
+        self.cell_at(layout, cord).map(u8::from)
+    }
+
+    /// Whether the position meets the `WinCondition` of `layout`.
+    pub fn is_solved(&self, layout: &Board) -> bool {
+        let on_exit = std::iter::once(&self.sokoban)
+            .chain(&self.agents)
+            .any(|cord| layout.get_cell(*cord).is_some_and(Cell::is_exit));
+        layout.win_condition.is_met(self.are_boxes_placed(layout), on_exit)
+    }
+
+    /// Whether every box stands on a goal of its colour or, under `Ruleset::Reverse`,
+    /// on a cell where the board has a box of its colour.
+    fn are_boxes_placed(&self, layout: &Board) -> bool {
+        match layout.ruleset {
+            Ruleset::Reverse => self.boxes.iter().all(|cord| {
+                layout.get_cell(*cord).is_some_and(Cell::has_box) && self.box_color(*cord) == layout.box_color(*cord)
+            }),
+            _ => self.boxes.iter().all(|cord| layout.is_goal(*cord) && self.box_color(*cord) == layout.goal_color(*cord)),
+        }
+    }
+}
+
+/// Colour of `cord` in a list sorted by position, plain if it isn't listed.
+fn color_at(colors: &[(Point, u8)], cord: Point) -> u8 {
+    colors
+        .binary_search_by_key(&cord, |(point, _)| *point)
+        .map_or(0, |index| colors[index].1)
+}
+
+fn sorted_colors(mut colors: Vec<(Point, u8)>) -> Vec<(Point, u8)> {
+    colors.retain(|(_, color)| *color != 0);
+    colors.sort();
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_board_as_string(board: &Board) -> String {
+        board.get_board_as_strings().unwrap().join("\n")
+    }
+
+    #[allow(dead_code)]
+    fn debug_board(board: &Board) {
+        print!("{}", get_board_as_string(board));
+    }
+
+    #[test]
+    fn test_get_set_state_single() {
+        let mut board = Board::new(Size { width: 1, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+
+        let expected_board = String::from("s");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_get_set_state_one_crate() {
+        let mut board = Board::new(Size { width: 4, height: 2 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 1 }, 5).unwrap();
+
+        let expected_board = String::from(".scX\n*.*S");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_get_set_state_all_available() {
+        let mut board = Board::new(Size { width: 7, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 0).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 3).unwrap();
+        board.set_state_at_cell(Point { x: 4, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 5, y: 0 }, 5).unwrap();
+        board.set_state_at_cell(Point { x: 6, y: 0 }, 6).unwrap();
+
+        let expected_board = String::from("*.cCsSX");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_set_unavailable_state() {
+        let mut board = Board::new(Size { width: 1, height: 1 });
+
+        assert_eq!(
+            Err(SokobanError::UnknownState(26)),
+            board.set_state_at_cell(Point { x: 0, y: 0 }, 26)
+        );
+        assert_eq!(
+            Err(SokobanError::UnsupportedCell(Cell::Ice)),
+            board.set_state_at_cell(Point { x: 0, y: 0 }, 7)
+        );
+    }
+
+    #[test]
+    fn test_set_state_beyond_the_field() {
+        let mut board = Board::new(Size { width: 1, height: 1 });
+
+        assert_eq!(
+            Err(SokobanError::OutOfField(Point { x: 1, y: 0 })),
+            board.set_state_at_cell(Point { x: 1, y: 0 }, 1)
+        );
+    }
+
+    #[test]
+    fn test_from_field_len_mismatch() {
+        let board = Board::from(vec![0u8; 3], Size { width: 2, height: 2 });
+        assert_eq!(Err(SokobanError::FieldLengthMismatch), board);
+    }
+
+    #[test]
+    fn test_make_step_on_invalid_board() {
+        let board = Board::new(Size { width: 2, height: 1 });
+        assert_eq!(Err(SokobanError::InvalidBoard), board.make_step(Direction::Forward));
+    }
+
+    #[test]
+    fn test_get_state_at_unavailable_position() {
+        let board = Board::new(Size { width: 2, height: 2 });
+
+        assert_eq!(Option::None, board.get_state_at_cell(Point { x: 3, y: 0}));
+        assert_eq!(Option::None, board.get_state_at_cell(Point { x: 0, y: 3}));
+        assert_eq!(Option::None, board.get_state_at_cell(Point { x: 3, y: 3}));
+    }
+
+    #[test]
+    fn test_try_validate_invalid_board_two_sokobans() {
+        let mut board = Board::new(Size { width: 9, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 5).unwrap();
+
+        board = board.validate_board();
+        assert!(!board.is_valid);
+    }
+
+    #[test]
+    fn test_try_validate_invalid_board_boxes_dests_not_eq() {
+        let mut board = Board::new(Size { width: 9, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 2).unwrap();
+
+        board = board.validate_board();
+        assert!(!board.is_valid);
+    }
+
+    #[test]
+    fn test_try_validate_valid_board() {
+        let mut board = Board::new(Size { width: 9, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 6).unwrap();
+
+        board = board.validate_board();
+        assert!(board.is_valid);
+    }
+
+    #[test]
+    fn test_make_one_step_move_box_on_destination() {
+        let mut board = Board::new(Size { width: 4, height: 2 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 1).unwrap();
+
+        board = board.validate_board();
+        board = board.make_step(Direction::Forward).unwrap();
+
+        let expected_board = String::from("..sC\n*.**");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_make_one_step_move_box_from_destionation() {
+        let mut board = Board::new(Size { width: 4, height: 2 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 3).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 1).unwrap();
+
+        board = board.validate_board();
+        board = board.make_step(Direction::Forward).unwrap();
+
+        let expected_board = String::from("..Sc\n*.**");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_make_one_step_move_sokoban() {
+        let mut board = Board::new(Size { width: 2, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 1).unwrap();
+
+        board = board.validate_board();
+        board = board.make_step(Direction::Forward).unwrap();
+
+        let expected_board = String::from(".s");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_make_one_step_move_sokoban_on_destination() {
+        let mut board = Board::new(Size { width: 3, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 2).unwrap();
+
+        board = board.validate_board();
+        board = board.make_step(Direction::Forward).unwrap();
+
+        let expected_board = String::from(".Sc");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_make_one_step_move_sokoban_out_of_field_left() {
+        let mut board = Board::new(Size { width: 1, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+       
+        board = board.validate_board();
+        board.make_step(Direction::Backward).unwrap();
+
+        let expected_board = String::from("s");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_make_one_step_move_sokoban_out_of_field_right() {
+        let mut board = Board::new(Size { width: 1, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+       
+        board = board.validate_board();
+        board = board.make_step(Direction::Forward).unwrap();
+
+        let expected_board = String::from("s");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_make_one_step_move_sokoban_out_of_field_up() {
+        let mut board = Board::new(Size { width: 2, height: 1 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 4).unwrap();
+
+        board = board.validate_board();
+        board = board.make_step(Direction::Up).unwrap();
+
+        let expected_board = String::from(".s");
+        assert_eq!(expected_board, get_board_as_string(&board));
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut board = Board::new(Size { width: 4, height: 2 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 5).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 3).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 1).unwrap();
+
+        board = board.validate_board();
+        let state = board.get_state().unwrap();
+
+        assert_eq!(state.sokoban, Point { x: 1, y: 0 });
+        assert_eq!(state.boxes, vec![Point { x: 2, y: 0 }, Point { x: 3, y: 0 }]);
+        assert_eq!(get_board_as_string(&board), get_board_as_string(&board.with_state(&state)));
+    }
+
+    #[test]
+    fn test_state_make_step_keeps_boxes_sorted() {
+        let mut board = Board::new(Size { width: 3, height: 3 });
+
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 1 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 2 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 0, y: 1 }, 6).unwrap();
+
+        let board = board.validate_board();
+        let mut state = board.get_state().unwrap();
+
+        assert!(state.make_step(&board, Direction::Down));
+        assert_eq!(state.boxes, vec![Point { x: 1, y: 2 }, Point { x: 2, y: 1 }]);
+        assert!(!state.make_step(&board, Direction::Down));
+        assert!(!state.is_solved(&board));
+    }
+
+    #[test]
+    fn test_run_simple_game() {
+        let mut board = Board::new(Size { width: 5, height: 4 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 0, y: 1 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 1 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 1 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 0, y: 2 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 2 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 2 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 2 }, 3).unwrap();
+
+        let mut board = board.validate_board();
+
+        let actions = [
+            Direction::Forward, 
+            Direction::Forward,
+            Direction::Backward,
+            Direction::Down
+        ];
+
+        let game_states = [
+            String::from(".scX*\n.c..*\n.X.C*\n*****"), 
+            String::from("..sC*\n.c..*\n.X.C*\n*****"), 
+            String::from(".s.C*\n.c..*\n.X.C*\n*****"),
+            String::from("...C*\n.s..*\n.C.C*\n*****")
+        ];
+
+        for (index, action) in actions.iter().enumerate() {
+            board = board.make_step(*action).unwrap();
+            assert_eq!(game_states[index], get_board_as_string(&board));
+        }   
+    }
+
+    #[test]
+    fn test_ice_box_slides_until_floor() {
+        let board = Board::from_xsb("#@$~~~.#").unwrap();
+        assert_eq!(board.ruleset, Ruleset::Ice);
+        assert_eq!(board.encoding, CellEncoding::Extended);
+
+        let board = board.make_step(Direction::Forward).unwrap();
+
+        assert_eq!(get_board_as_string(&board), "*.s~~~C*");
+        assert!(board.get_state().unwrap().is_solved(&board));
+    }
+
+    #[test]
+    fn test_ice_slide_stops_at_box_and_wall() {
+        let board = Board::from_xsb("#@$~~$.#\n#~~~# .#").unwrap();
+
+        let pushed = board.make_step(Direction::Forward).unwrap();
+        assert_eq!(get_board_as_string(&pushed), "*.s~icX*\n*~~~*.X*");
+
+        let mut state = board.get_state().unwrap();
+        state.sokoban = Point { x: 1, y: 1 };
+        assert!(state.make_step(&board, Direction::Forward));
+        assert_eq!(state.sokoban, Point { x: 3, y: 1 });
+    }
+
+    #[test]
+    fn test_ice_needs_the_ice_ruleset() {
+        let board = Board::from_xsb("#@$~.#").unwrap().with_ruleset(Ruleset::Classic);
+        assert!(!board.is_valid);
+
+        let board = board.with_ruleset(Ruleset::Ice);
+        assert!(board.is_valid);
+        assert_eq!(board.to_xsb().unwrap(), "#@$~.#");
+    }
+
+    #[test]
+    fn test_moved_points() {
+        let board = Board::from_xsb("#@$~~.#").unwrap();
+        let previous = board.get_state().unwrap();
+        let mut state = previous.clone();
+        state.make_step(&board, Direction::Forward);
+
+        let mut points = state.moved_points(&previous);
+        points.sort();
+        assert_eq!(points, vec![
+            Point { x: 1, y: 0 },
+            Point { x: 2, y: 0 },
+            Point { x: 2, y: 0 },
+            Point { x: 5, y: 0 },
+        ]);
+    }
+
+    #[test]
+    fn test_teleporters_need_exactly_one_partner() {
+        let board = Board::from_xsb("#@$.^ ^#").unwrap();
+        assert_eq!(board.teleporters, vec![(Point { x: 4, y: 0 }, Point { x: 6, y: 0 })]);
+        assert!(board.is_valid);
+
+        let unpaired = board.clone().with_teleporters(Vec::new());
+        assert!(!unpaired.is_valid);
+
+        let twice = board.clone().with_teleporters(vec![
+            (Point { x: 4, y: 0 }, Point { x: 6, y: 0 }),
+            (Point { x: 6, y: 0 }, Point { x: 4, y: 0 }),
+        ]);
+        assert!(!twice.is_valid);
+
+        let to_floor = board.with_teleporters(vec![(Point { x: 4, y: 0 }, Point { x: 5, y: 0 })]);
+        assert!(!to_floor.is_valid);
+    }
+
+    #[test]
+    fn test_teleporter_moves_the_sokoban_to_a_free_partner() {
+        let board = Board::from_xsb("#@^ #\n#.$^#").unwrap();
+
+        let stepped = board.make_step(Direction::Forward).unwrap();
+        assert_eq!(get_board_as_string(&stepped), "*.T.*\n*XcQ*");
+
+        // A box on the partner keeps the sokoban on the teleporter it stepped onto.
+        let mut blocked = board.get_state().unwrap();
+        blocked.boxes = vec![Point { x: 3, y: 1 }];
+        assert!(blocked.make_step(&board, Direction::Forward));
+        assert_eq!(blocked.sokoban, Point { x: 2, y: 0 });
+    }
+
+    #[test]
+    fn test_teleporter_sends_a_pushed_box_out_of_its_partner() {
+        let board = Board::from_xsb("#@$^ #\n#. ^ #").unwrap();
+
+        let board = board.make_step(Direction::Forward).unwrap();
+        assert_eq!(get_board_as_string(&board), "*.sT.*\n*X.q.*");
+
+        // With the partner taken, the box stays on the teleporter it was pushed into.
+        let board = Board::from_xsb("#@$^ #\n#.$^.#").unwrap();
+        let mut state = board.get_state().unwrap();
+        state.boxes = vec![Point { x: 2, y: 0 }, Point { x: 3, y: 1 }];
+        assert!(state.make_step(&board, Direction::Forward));
+        assert_eq!(state.boxes, vec![Point { x: 3, y: 0 }, Point { x: 3, y: 1 }]);
+    }
+
+    #[test]
+    fn test_colored_boxes_count_per_color() {
+        let board = Board::from_xsb("#@$$..#").unwrap();
+        let red = vec![(Point { x: 3, y: 0 }, 1)];
+
+        assert!(!board.clone().with_colors(red.clone(), Vec::new()).is_valid);
+        assert!(!board.clone().with_colors(red.clone(), vec![(Point { x: 2, y: 0 }, 1)]).is_valid);
+        let beyond = vec![(Point { x: 3, y: 0 }, MAX_COLORS)];
+        assert!(!board.clone().with_colors(beyond, vec![(Point { x: 5, y: 0 }, MAX_COLORS)]).is_valid);
+
+        let board = board.with_colors(red, vec![(Point { x: 4, y: 0 }, 1), (Point { x: 5, y: 0 }, 0)]);
+        assert!(board.is_valid);
+        assert_eq!(board.goal_colors, vec![(Point { x: 4, y: 0 }, 1)]);
+    }
+
+    #[test]
+    fn test_colored_box_only_counts_on_its_goal() {
+        let board = Board::from_xsb("#@$.$.#")
+            .unwrap()
+            .with_colors(vec![(Point { x: 2, y: 0 }, 1)], vec![(Point { x: 5, y: 0 }, 1)]);
+        assert!(board.is_valid);
+
+        let pushed = board.make_step(Direction::Forward).unwrap();
+        assert_eq!(pushed.box_colors, vec![(Point { x: 3, y: 0 }, 1)]);
+
+        let mut state = board.get_state().unwrap();
+        state.boxes = vec![Point { x: 3, y: 0 }, Point { x: 5, y: 0 }];
+        state.box_colors = vec![(Point { x: 3, y: 0 }, 1)];
+        assert!(!state.is_solved(&board));
+
+        state.box_colors = vec![(Point { x: 5, y: 0 }, 1)];
+        assert!(state.is_solved(&board));
+    }
+
+    #[test]
+    fn test_colored_box_moves_its_hash() {
+        let board = Board::from_xsb("#@$ .$.#")
+            .unwrap()
+            .with_colors(vec![(Point { x: 2, y: 0 }, 3)], vec![(Point { x: 4, y: 0 }, 3)]);
+        let mut state = board.get_state().unwrap();
+
+        assert!(state.make_step(&board, Direction::Forward));
+        assert_eq!(state.hash, board.with_state(&state).zobrist_hash().unwrap());
+        assert_ne!(state.hash, zobrist::hash_position(state.sokoban, &state.boxes));
+    }
+
+    fn get_mechanism_board() -> Board {
+        // A plate at (2, 1) opens the gate at (4, 0); the one-way cell at (3, 0)
+        // can only be crossed to the right.
+        let mut board = Board::new_with_encoding(Size { width: 6, height: 2 }, CellEncoding::Extended);
+        let cells = [Cell::Sokoban, Cell::Box, Cell::Floor, Cell::OneWay, Cell::Gate, Cell::Goal];
+        for (x, cell) in cells.into_iter().enumerate() {
+            board.set_cell(Point { x, y: 0 }, cell).unwrap();
+        }
+        board.set_cell(Point { x: 1, y: 1 }, Cell::Floor).unwrap();
+        board.set_cell(Point { x: 2, y: 1 }, Cell::Plate).unwrap();
+        board.set_cell(Point { x: 3, y: 1 }, Cell::Box).unwrap();
+        board.set_cell(Point { x: 4, y: 1 }, Cell::Goal).unwrap();
+
+        board
+            .with_one_way(vec![(Point { x: 3, y: 0 }, Direction::Forward)])
+            .with_gates(vec![(Point { x: 2, y: 1 }, Point { x: 4, y: 0 })])
+    }
+
+    #[test]
+    fn test_one_way_and_gate_need_configuration() {
+        let board = get_mechanism_board();
+        assert!(board.is_valid);
+
+        assert!(!board.clone().with_one_way(Vec::new()).is_valid);
+        assert!(!board.clone().with_gates(Vec::new()).is_valid);
+        assert!(!board.clone().with_gates(vec![(Point { x: 1, y: 1 }, Point { x: 4, y: 0 })]).is_valid);
+    }
+
+    #[test]
+    fn test_gate_opens_while_a_box_rests_on_its_plate() {
+        let board = get_mechanism_board();
+        let mut state = board.get_state().unwrap();
+
+        // The box stops in front of the closed gate.
+        assert!(state.make_step(&board, Direction::Forward));
+        assert!(state.make_step(&board, Direction::Forward));
+        assert!(!state.make_step(&board, Direction::Forward));
+        assert_eq!(board.with_state(&state).get_board_as_strings().unwrap()[0], "..syGX");
+
+        // With a box on the plate, the gate opens and the box goes through.
+        state.boxes = vec![Point { x: 2, y: 1 }, Point { x: 3, y: 0 }];
+        assert!(state.is_gate_open(&board, Point { x: 4, y: 0 }));
+        assert!(state.make_step(&board, Direction::Forward));
+        assert_eq!(board.with_state(&state).get_board_as_strings().unwrap()[0], "...YhX");
+    }
+
+    #[test]
+    fn test_one_way_blocks_the_other_directions() {
+        let board = get_mechanism_board();
+        let mut state = board.get_state().unwrap();
+
+        state.sokoban = Point { x: 3, y: 1 };
+        state.boxes = vec![Point { x: 1, y: 0 }, Point { x: 4, y: 1 }];
+        assert!(!state.make_step(&board, Direction::Up));
+
+        state.sokoban = Point { x: 3, y: 0 };
+        assert!(!state.make_step(&board, Direction::Backward));
+        assert!(!state.make_step(&board, Direction::Down));
+    }
+
+    #[test]
+    fn test_board_needs_as_many_sokobans_as_agents() {
+        let board = Board::from_xsb("#@$.@#").unwrap();
+        assert!(!board.is_valid);
+
+        let board = board.with_agents(2);
+        assert!(board.is_valid);
+        assert_eq!(board.sokoban_position, Some(Point { x: 1, y: 0 }));
+        assert_eq!(board.get_state().unwrap().agents, vec![Point { x: 4, y: 0 }]);
+        assert!(!board.clone().with_agents(0).is_valid);
+    }
+
+    #[test]
+    fn test_agents_move_separately_and_block_each_other() {
+        let board = Board::from_xsb("#@ $.#\n#  @ #").unwrap().with_agents(2);
+        let mut state = board.get_state().unwrap();
+
+        assert!(state.make_agent_step(&board, 1, Direction::Backward));
+        assert_eq!(state.agents, vec![Point { x: 2, y: 1 }]);
+        assert!(!state.make_agent_step(&board, 2, Direction::Backward));
+
+        // A sokoban can't walk into another.
+        assert!(state.make_step(&board, Direction::Down));
+        assert!(!state.make_step(&board, Direction::Forward));
+        assert!(state.make_agent_step(&board, 1, Direction::Up));
+        assert!(state.make_agent_step(&board, 1, Direction::Forward));
+
+        assert!(state.is_solved(&board));
+        assert_eq!(state.hash, board.with_state(&state).zobrist_hash().unwrap());
+        assert_eq!(get_board_as_string(&board.with_state(&state)), "*..sC*\n*s...*");
+
+        // Nor push a box into one.
+        let board = Board::from_xsb("#@$@.#").unwrap().with_agents(2);
+        assert_eq!(board.make_step(Direction::Forward), Ok(board.clone()));
+    }
+
+    #[test]
+    fn test_reverse_starts_solved_and_pulls_boxes_back() {
+        let board = Board::from_xsb("# .$@ #").unwrap().with_ruleset(Ruleset::Reverse);
+        let mut state = board.start_state().unwrap();
+        assert_eq!(state.boxes, vec![Point { x: 2, y: 0 }]);
+        assert!(!state.is_solved(&board));
+
+        assert!(state.make_step(&board, Direction::Backward));
+        // Boxes can't be pushed.
+        assert!(!state.make_step(&board, Direction::Backward));
+        assert!(state.make_step(&board, Direction::Forward));
+
+        assert_eq!(state.boxes, vec![Point { x: 3, y: 0 }]);
+        assert!(state.is_solved(&board));
+        assert_eq!(state.hash, board.with_state(&state).zobrist_hash().unwrap());
+        assert!(!Board::from_xsb("#+$ #").unwrap().with_ruleset(Ruleset::Reverse).is_valid);
+    }
+
+    #[test]
+    fn test_toroidal_board_wraps_the_sokoban_and_boxes() {
+        let board = Board::from_xsb("$.  @\n  #  ").unwrap();
+        let mut state = board.get_state().unwrap();
+        assert!(!state.make_step(&board, Direction::Forward));
+
+        let board = board.with_topology(Topology::Toroidal);
+        assert!(board.is_valid);
+        assert_eq!(board.neighbor(Point { x: 0, y: 0 }, Direction::Up), Some(Point { x: 0, y: 1 }));
+        assert_eq!(board.neighbor(Point { x: 4, y: 1 }, Direction::Down), Some(Point { x: 4, y: 0 }));
+
+        // The sokoban steps off the right edge and pushes the box in from the left.
+        assert!(state.make_step(&board, Direction::Forward));
+        assert_eq!(state.sokoban, Point { x: 0, y: 0 });
+        assert_eq!(state.boxes, vec![Point { x: 1, y: 0 }]);
+        assert!(state.is_solved(&board));
+        assert_eq!(state.hash, board.with_state(&state).zobrist_hash().unwrap());
+    }
+
+    fn get_escape_board() -> Board {
+        let mut board = Board::new_with_encoding(Size { width: 4, height: 2 }, CellEncoding::Extended);
+        let cells = [Cell::Sokoban, Cell::Box, Cell::Goal, Cell::Wall, Cell::Floor, Cell::Floor, Cell::Floor, Cell::Exit];
+        for (index, cell) in cells.into_iter().enumerate() {
+            board.set_cell(Point { x: index % 4, y: index / 4 }, cell).unwrap();
+        }
+        board.validate_board()
+    }
+
+    #[test]
+    fn test_win_conditions_with_an_exit() {
+        let board = get_escape_board().with_win_condition(WinCondition::BoxesOnGoalsAndExit);
+        assert!(board.is_valid);
+        let mut state = board.get_state().unwrap();
+
+        assert!(state.make_step(&board, Direction::Forward));
+        assert!(!state.is_solved(&board));
+        for direction in [Direction::Down, Direction::Forward, Direction::Forward] {
+            assert!(state.make_step(&board, direction));
+        }
+        assert!(state.is_solved(&board));
+
+        // Reaching the exit is enough, wherever the box is.
+        let board = board.with_win_condition(WinCondition::ReachExit);
+        let mut state = board.get_state().unwrap();
+        for direction in [Direction::Down, Direction::Forward, Direction::Forward, Direction::Forward] {
+            assert!(state.make_step(&board, direction));
+        }
+        assert!(state.is_solved(&board));
+        assert!(!state.is_solved(&board.with_win_condition(WinCondition::BoxesOnGoals)));
+
+        let board = Board::from_xsb("#@$.#").unwrap();
+        assert!(!board.with_win_condition(WinCondition::ReachExit).is_valid);
+    }
+}
@@ -0,0 +1,297 @@
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::auxiliary::*;
+use crate::error::*;
+
+/// Content of a cell. The discriminant is the state stored in `Board::field`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[repr(u8)]
+pub enum Cell {
+    Wall = 0,
+    Floor = 1,
+    Box = 2,
+    BoxOnGoal = 3,
+    Sokoban = 4,
+    SokobanOnGoal = 5,
+    Goal = 6,
+    /// Boxes and the sokoban slide over ice, see `Ruleset::Ice`.
+    Ice = 7,
+    BoxOnIce = 8,
+    SokobanOnIce = 9,
+    /// Sends what enters it out of its partner, see `Board::teleporters`.
+    Teleporter = 10,
+    BoxOnTeleporter = 11,
+    SokobanOnTeleporter = 12,
+    /// Can only be crossed in one direction, see `Board::one_way`.
+    OneWay = 13,
+    BoxOnOneWay = 14,
+    SokobanOnOneWay = 15,
+    /// Opens the gates linked to it while a box rests on it, see `Board::gates`.
+    Plate = 16,
+    BoxOnPlate = 17,
+    SokobanOnPlate = 18,
+    /// A closed gate blocks like a wall. Gates are drawn open or closed from the
+    /// position, so a board may store either.
+    Gate = 19,
+    OpenGate = 20,
+    BoxOnGate = 21,
+    SokobanOnGate = 22,
+    /// Where the sokoban escapes on boards won by reaching an exit, see `WinCondition`.
+    Exit = 23,
+    BoxOnExit = 24,
+    SokobanOnExit = 25,
+}
+
+impl Cell {
+    /// The seven cells of classic Sokoban, the ones that fit `CellEncoding::Classic`.
+    pub const CLASSIC: [Cell; 7] = [
+        Cell::Wall,
+        Cell::Floor,
+        Cell::Box,
+        Cell::BoxOnGoal,
+        Cell::Sokoban,
+        Cell::SokobanOnGoal,
+        Cell::Goal,
+    ];
+
+    /// Every cell, in the order of the states.
+    pub const ALL: [Cell; 26] = [
+        Cell::Wall,
+        Cell::Floor,
+        Cell::Box,
+        Cell::BoxOnGoal,
+        Cell::Sokoban,
+        Cell::SokobanOnGoal,
+        Cell::Goal,
+        Cell::Ice,
+        Cell::BoxOnIce,
+        Cell::SokobanOnIce,
+        Cell::Teleporter,
+        Cell::BoxOnTeleporter,
+        Cell::SokobanOnTeleporter,
+        Cell::OneWay,
+        Cell::BoxOnOneWay,
+        Cell::SokobanOnOneWay,
+        Cell::Plate,
+        Cell::BoxOnPlate,
+        Cell::SokobanOnPlate,
+        Cell::Gate,
+        Cell::OpenGate,
+        Cell::BoxOnGate,
+        Cell::SokobanOnGate,
+        Cell::Exit,
+        Cell::BoxOnExit,
+        Cell::SokobanOnExit,
+    ];
+
+    pub fn is_wall(self) -> bool {
+        self == Cell::Wall
+    }
+
+    pub fn is_goal(self) -> bool {
+        matches!(self, Cell::BoxOnGoal | Cell::SokobanOnGoal | Cell::Goal)
+    }
+
+    pub fn is_ice(self) -> bool {
+        matches!(self, Cell::Ice | Cell::BoxOnIce | Cell::SokobanOnIce)
+    }
+
+    pub fn is_teleporter(self) -> bool {
+        matches!(self, Cell::Teleporter | Cell::BoxOnTeleporter | Cell::SokobanOnTeleporter)
+    }
+
+    pub fn is_one_way(self) -> bool {
+        matches!(self, Cell::OneWay | Cell::BoxOnOneWay | Cell::SokobanOnOneWay)
+    }
+
+    pub fn is_plate(self) -> bool {
+        matches!(self, Cell::Plate | Cell::BoxOnPlate | Cell::SokobanOnPlate)
+    }
+
+    /// Gates, open or closed.
+    pub fn is_gate(self) -> bool {
+        matches!(self, Cell::Gate | Cell::OpenGate | Cell::BoxOnGate | Cell::SokobanOnGate)
+    }
+
+    pub fn is_exit(self) -> bool {
+        matches!(self, Cell::Exit | Cell::BoxOnExit | Cell::SokobanOnExit)
+    }
+
+    pub fn has_box(self) -> bool {
+        matches!(
+            self,
+            Cell::Box
+                | Cell::BoxOnGoal
+                | Cell::BoxOnIce
+                | Cell::BoxOnTeleporter
+                | Cell::BoxOnOneWay
+                | Cell::BoxOnPlate
+                | Cell::BoxOnGate
+                | Cell::BoxOnExit
+        )
+    }
+
+    pub fn has_sokoban(self) -> bool {
+        matches!(
+            self,
+            Cell::Sokoban
+                | Cell::SokobanOnGoal
+                | Cell::SokobanOnIce
+                | Cell::SokobanOnTeleporter
+                | Cell::SokobanOnOneWay
+                | Cell::SokobanOnPlate
+                | Cell::SokobanOnGate
+                | Cell::SokobanOnExit
+        )
+    }
+
+    pub fn is_classic(self) -> bool {
+        Self::CLASSIC.contains(&self)
+    }
+
+    /// The cell without its box or sokoban. A gate something stood on stays open.
+    pub fn terrain(self) -> Self {
+        match self {
+            Cell::Wall | Cell::Gate => self,
+            _ if self.is_goal() => Cell::Goal,
+            _ if self.is_ice() => Cell::Ice,
+            _ if self.is_teleporter() => Cell::Teleporter,
+            _ if self.is_one_way() => Cell::OneWay,
+            _ if self.is_plate() => Cell::Plate,
+            _ if self.is_gate() => Cell::OpenGate,
+            _ if self.is_exit() => Cell::Exit,
+            _ => Cell::Floor,
+        }
+    }
+
+    /// This cell's terrain with a box on it. Walls stay walls.
+    pub fn with_box(self) -> Self {
+        match self.terrain() {
+            Cell::Wall => Cell::Wall,
+            Cell::Goal => Cell::BoxOnGoal,
+            Cell::Ice => Cell::BoxOnIce,
+            Cell::Teleporter => Cell::BoxOnTeleporter,
+            Cell::OneWay => Cell::BoxOnOneWay,
+            Cell::Plate => Cell::BoxOnPlate,
+            Cell::Gate | Cell::OpenGate => Cell::BoxOnGate,
+            Cell::Exit => Cell::BoxOnExit,
+            _ => Cell::Box,
+        }
+    }
+
+    /// This cell's terrain with the sokoban on it. Walls stay walls.
+    pub fn with_sokoban(self) -> Self {
+        match self.terrain() {
+            Cell::Wall => Cell::Wall,
+            Cell::Goal => Cell::SokobanOnGoal,
+            Cell::Ice => Cell::SokobanOnIce,
+            Cell::Teleporter => Cell::SokobanOnTeleporter,
+            Cell::OneWay => Cell::SokobanOnOneWay,
+            Cell::Plate => Cell::SokobanOnPlate,
+            Cell::Gate | Cell::OpenGate => Cell::SokobanOnGate,
+            Cell::Exit => Cell::SokobanOnExit,
+            _ => Cell::Sokoban,
+        }
+    }
+}
+
+impl TryFrom<u8> for Cell {
+    type Error = SokobanError;
+
+    fn try_from(state: u8) -> Result<Self, Self::Error> {
+        Cell::ALL.get(state as usize).copied().ok_or(SokobanError::UnknownState(state))
+    }
+}
+
+impl From<Cell> for u8 {
+    fn from(cell: Cell) -> Self {
+        cell as u8
+    }
+}
+
+/// How cells are packed into `Board::field`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CellEncoding {
+    /// Two cells per byte, high nibble first. Holds the classic cells only.
+    #[default]
+    Classic,
+    /// One cell per byte, for boards that use extended tile sets.
+    Extended,
+}
+
+impl CellEncoding {
+    /// Length of the packed field of a board of `size`.
+    pub fn field_len(self, size: Size) -> usize {
+        let cells = size.width * size.height;
+        match self {
+            CellEncoding::Classic => cells.div_ceil(2),
+            CellEncoding::Extended => cells,
+        }
+    }
+
+    pub fn supports(self, cell: Cell) -> bool {
+        match self {
+            CellEncoding::Classic => cell.is_classic(),
+            CellEncoding::Extended => true,
+        }
+    }
+
+    /// The state at `index` of `field`, unchecked.
+    pub(crate) fn read(self, field: &[u8], index: usize) -> u8 {
+        match self {
+            CellEncoding::Classic if index % 2 == 1 => field[index / 2] & 0x0F,
+            CellEncoding::Classic => field[index / 2] >> 4,
+            CellEncoding::Extended => field[index],
+        }
+    }
+
+    pub(crate) fn write(self, field: &mut [u8], index: usize, state: u8) {
+        match self {
+            CellEncoding::Classic if index % 2 == 1 => field[index / 2] = (field[index / 2] & 0xF0) | state,
+            CellEncoding::Classic => field[index / 2] = (state << 4) | (field[index / 2] & 0x0F),
+            CellEncoding::Extended => field[index] = state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_state_round_trip() {
+        for cell in Cell::ALL {
+            assert_eq!(Cell::try_from(u8::from(cell)), Ok(cell));
+        }
+        assert_eq!(Cell::try_from(26), Err(SokobanError::UnknownState(26)));
+    }
+
+    #[test]
+    fn test_cell_contents() {
+        assert_eq!(Cell::SokobanOnGoal.terrain(), Cell::Goal);
+        assert_eq!(Cell::Goal.with_box(), Cell::BoxOnGoal);
+        assert_eq!(Cell::Sokoban.with_box(), Cell::Box);
+        assert_eq!(Cell::BoxOnGoal.with_sokoban(), Cell::SokobanOnGoal);
+        assert_eq!(Cell::Wall.with_sokoban(), Cell::Wall);
+        assert_eq!(Cell::SokobanOnIce.with_box(), Cell::BoxOnIce);
+        assert_eq!(Cell::BoxOnTeleporter.terrain(), Cell::Teleporter);
+        assert_eq!(Cell::SokobanOnGate.terrain(), Cell::OpenGate);
+        assert_eq!(Cell::Gate.with_box(), Cell::BoxOnGate);
+        assert_eq!(Cell::BoxOnExit.with_sokoban(), Cell::SokobanOnExit);
+        assert!(!CellEncoding::Classic.supports(Cell::Ice));
+    }
+
+    #[test]
+    fn test_field_len() {
+        let size = Size { width: 3, height: 3 };
+        assert_eq!(CellEncoding::Classic.field_len(size), 5);
+        assert_eq!(CellEncoding::Extended.field_len(size), 9);
+    }
+}
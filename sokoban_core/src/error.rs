@@ -0,0 +1,43 @@
+use std::fmt;
+
+use crate::auxiliary::*;
+use crate::cell::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SokobanError {
+    /// Length of a packed field doesn't match the size of the board.
+    FieldLengthMismatch,
+    /// Cell state that isn't a known `Cell`.
+    UnknownState(u8),
+    /// Attempt to set a cell beyond the field.
+    OutOfField(Point),
+    /// The board has no single sokoban to move.
+    InvalidBoard,
+    /// Character that has no meaning in the XSB or LURD notation.
+    UnknownSymbol(char),
+    /// Cell that the encoding of the board, or the XSB notation, can't hold.
+    UnsupportedCell(Cell),
+    /// Colour of a box or goal beyond `MAX_COLORS`.
+    UnknownColor(u8),
+    /// A list of colours that doesn't have one colour per box or goal.
+    ColorCountMismatch,
+}
+
+impl fmt::Display for SokobanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SokobanError::FieldLengthMismatch => {
+                write!(f, "Passed field_len and passed vector length don't match")
+            },
+            SokobanError::UnknownState(_) => write!(f, "There is no such available state"),
+            SokobanError::OutOfField(_) => write!(f, "Attempt of setting a value beyond the field"),
+            SokobanError::InvalidBoard => write!(f, "Invalid board"),
+            SokobanError::UnknownSymbol(symbol) => write!(f, "Unknown symbol '{}'", symbol),
+            SokobanError::UnsupportedCell(cell) => write!(f, "{:?} doesn't fit the encoding or notation of the board", cell),
+            SokobanError::UnknownColor(color) => write!(f, "There is no colour {}", color),
+            SokobanError::ColorCountMismatch => write!(f, "Number of colours doesn't match the boxes or goals"),
+        }
+    }
+}
+
+impl std::error::Error for SokobanError {}
@@ -0,0 +1,20 @@
+//! Sokoban rules shared by the NEAR contract and native tools. The crate has no
+//! NEAR dependency: errors are returned as `SokobanError`, and Borsh/serde
+//! support is behind the `borsh` and `serde` features, PNG and GIF export behind
+//! `render`.
+
+pub mod auxiliary;
+pub mod board;
+pub mod cell;
+pub mod error;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod svg;
+pub mod xsb;
+pub mod zobrist;
+
+pub use crate::auxiliary::*;
+pub use crate::board::*;
+pub use crate::cell::*;
+pub use crate::error::*;
+pub use crate::svg::*;
@@ -0,0 +1,510 @@
+//! Raster export for native tools: boards as PNG images and move lists as animated
+//! GIF replays. Needs the `render` feature and isn't built into the contract.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::auxiliary::*;
+use crate::board::*;
+use crate::cell::*;
+use crate::error::*;
+use crate::svg::Theme;
+
+/// Sprite sheets have at least the classic tiles, and at most one per cell.
+const MIN_TILES: usize = Cell::CLASSIC.len();
+const MAX_TILES: usize = Cell::ALL.len();
+
+#[derive(Debug)]
+pub enum RenderError {
+    Board(SokobanError),
+    /// The sprite sheet isn't a row of square tiles.
+    InvalidSprites(String),
+    /// The image is larger than the format allows.
+    TooLarge,
+    Png(String),
+    Gif(String),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Board(error) => write!(f, "{}", error),
+            RenderError::InvalidSprites(reason) => write!(f, "Invalid sprite sheet: {}", reason),
+            RenderError::TooLarge => write!(f, "The image is too large"),
+            RenderError::Png(error) => write!(f, "PNG error: {}", error),
+            RenderError::Gif(error) => write!(f, "GIF error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<SokobanError> for RenderError {
+    fn from(error: SokobanError) -> Self {
+        RenderError::Board(error)
+    }
+}
+
+impl From<png::EncodingError> for RenderError {
+    fn from(error: png::EncodingError) -> Self {
+        RenderError::Png(error.to_string())
+    }
+}
+
+impl From<png::DecodingError> for RenderError {
+    fn from(error: png::DecodingError) -> Self {
+        RenderError::Png(error.to_string())
+    }
+}
+
+impl From<gif::EncodingError> for RenderError {
+    fn from(error: gif::EncodingError) -> Self {
+        RenderError::Gif(error.to_string())
+    }
+}
+
+/// Square RGBA tiles in the order of `Cell::ALL`: wall, floor, box, box on goal,
+/// sokoban, sokoban on goal, goal, then the extended cells. Sprite sheets may stop
+/// after the classic cells; boards with other cells can't be drawn with them.
+///
+/// Coloured boxes and goals get a square mark in the middle of their tile.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Tileset {
+    tile_size: u32,
+    tiles: Vec<Vec<u8>>,
+    /// Marks of colours 1 and up, see `Palette::colors`.
+    color_marks: Vec<[u8; 4]>,
+}
+
+impl Tileset {
+    /// Built-in flat tiles in the colours of `theme`, drawn like `Board::to_svg` does.
+    pub fn flat(tile_size: u32, theme: Theme) -> Self {
+        let palette = theme.palette();
+        let wall = parse_color(palette.wall);
+        let floor = parse_color(palette.floor);
+        let goal = parse_color(palette.goal);
+        let sokoban = parse_color(palette.sokoban);
+        let ice = parse_color(palette.ice);
+        let teleporter = parse_color(palette.teleporter);
+        let mechanism = parse_color(palette.mechanism);
+
+        let size = tile_size as f32;
+        let half = size / 2.0;
+        let draw = |cell: Cell| {
+            let mut tile = Vec::with_capacity((tile_size * tile_size * 4) as usize);
+            for y in 0..tile_size {
+                for x in 0..tile_size {
+                    let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                    let distance = ((px - half).powi(2) + (py - half).powi(2)).sqrt();
+                    let edge = px.min(py).min(size - px).min(size - py);
+
+                    let on_ring = (distance - half * 0.4).abs() <= size * 0.04;
+                    let on_portal = (distance - half * 0.8).abs() <= size * 0.04;
+                    let on_plate = (edge - size * 0.2).abs() <= size * 0.03;
+                    let ground = if cell.is_ice() { ice } else { floor };
+                    let color = match cell {
+                        Cell::Wall => wall,
+                        Cell::Gate => mechanism,
+                        _ if cell.is_gate() && edge < size * 0.1 => mechanism,
+                        _ if cell.has_box() && edge < size * 0.1 => ground,
+                        _ if cell.has_box() && edge < size * 0.16 => wall,
+                        _ if cell.has_box() && cell.is_goal() => parse_color(palette.box_on_goal),
+                        _ if cell.has_box() => parse_color(palette.box_fill),
+                        _ if cell.has_sokoban() && distance <= half * 0.7 => sokoban,
+                        _ if cell.is_goal() && on_ring => goal,
+                        _ if cell.is_teleporter() && on_portal => teleporter,
+                        _ if cell.is_plate() && on_plate => mechanism,
+                        _ if cell.is_exit() && on_plate => goal,
+                        // Tiles don't know the direction of a one-way cell, so it gets a dot.
+                        _ if cell.is_one_way() && distance <= half * 0.2 => mechanism,
+                        _ => ground,
+                    };
+                    tile.extend_from_slice(&color);
+                }
+            }
+            tile
+        };
+
+        Self {
+            tile_size,
+            tiles: Cell::ALL.into_iter().map(draw).collect(),
+            color_marks: palette.colors.iter().map(|color| parse_color(color)).collect(),
+        }
+    }
+
+    /// Reads a PNG with the tiles side by side, in the order of `Cell::ALL`. Colours
+    /// are marked as in the default theme.
+    pub fn from_sprite_sheet(png_bytes: &[u8]) -> Result<Self, RenderError> {
+        let mut decoder = png::Decoder::new(png_bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        let (width, height) = (info.width, info.height);
+        let count = width.checked_div(height).unwrap_or(0) as usize;
+        if width % height.max(1) != 0 || !(MIN_TILES..=MAX_TILES).contains(&count) {
+            return Err(RenderError::InvalidSprites(format!(
+                "expected {} to {} square tiles in a row, got {}x{}",
+                MIN_TILES, MAX_TILES, width, height
+            )));
+        }
+
+        let rgba: Vec<u8> = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer.chunks(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => buffer.chunks(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+            png::ColorType::Indexed => {
+                return Err(RenderError::InvalidSprites(String::from("indexed colours weren't expanded")))
+            },
+        };
+
+        let row = (width * 4) as usize;
+        let tile_row = (height * 4) as usize;
+        let tiles = (0..count)
+            .map(|index| {
+                rgba.chunks(row)
+                    .flat_map(|line| &line[index * tile_row..(index + 1) * tile_row])
+                    .copied()
+                    .collect()
+            })
+            .collect();
+
+        let color_marks = Theme::default().palette().colors.iter().map(|color| parse_color(color)).collect();
+        Ok(Self { tile_size: height, tiles, color_marks })
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    fn tile(&self, cell: Cell) -> Result<&[u8], SokobanError> {
+        self.tiles.get(cell as usize).map(Vec::as_slice).ok_or(SokobanError::UnsupportedCell(cell))
+    }
+
+    /// Distinct colours of all tiles, if there are few enough for a GIF palette.
+    fn palette(&self) -> Option<HashMap<[u8; 3], u8>> {
+        let mut colors = HashMap::new();
+        let marks = self.color_marks.iter().map(|mark| mark.as_slice());
+        for pixel in self.tiles.iter().flat_map(|tile| tile.chunks(4)).chain(marks) {
+            let next = colors.len();
+            colors.entry([pixel[0], pixel[1], pixel[2]]).or_insert(next);
+            if colors.len() > 256 {
+                return None;
+            }
+        }
+
+        Some(colors.into_iter().map(|(color, index)| (color, index as u8)).collect())
+    }
+}
+
+impl Default for Tileset {
+    fn default() -> Self {
+        Self::flat(32, Theme::default())
+    }
+}
+
+/// Timing of an animated replay, in hundredths of a second.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReplayOptions {
+    pub frame_delay: u16,
+    /// How long the final position stays before the animation loops.
+    pub final_delay: u16,
+    pub repeat: bool,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self { frame_delay: 20, final_delay: 150, repeat: true }
+    }
+}
+
+impl Board {
+    /// The board as RGBA pixels, row by row. Tiles are placed by `drawn_at`, and what
+    /// no tile covers is the colour of the middle of the wall tile.
+    pub fn to_rgba(&self, tiles: &Tileset) -> Result<Vec<u8>, SokobanError> {
+        let tile_size = tiles.tile_size as usize;
+        let (columns, rows) = self.drawn_size();
+        let row = drawn_left(columns, tile_size) * 4;
+        let middle = (tile_size / 2 * tile_size + tile_size / 2) * 4;
+        let background = &tiles.tile(Cell::Wall)?[middle..middle + 4];
+        let mut pixels = background.repeat(row / 4 * rows * tile_size);
+
+        let mark_size = (tile_size / 4).max(1);
+        let mark_start = (tile_size - mark_size) / 2;
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let cord = Point { x, y };
+                let Some((column, drawn_row)) = self.drawn_at(cord) else {
+                    continue;
+                };
+                let (left, top) = (drawn_left(column, tile_size), drawn_row * tile_size);
+                let cell = self.get_cell(cord).unwrap();
+                let (box_color, goal_color) = (self.box_color(cord), self.goal_color(cord));
+                // A box on a goal of another colour isn't placed, so it's drawn as a plain box.
+                let shown = if cell == Cell::BoxOnGoal && box_color != goal_color { Cell::Box } else { cell };
+                let tile = tiles.tile(shown)?;
+                for (line, pixels_line) in tile.chunks(tile_size * 4).enumerate() {
+                    let start = (top + line) * row + left * 4;
+                    pixels[start..start + tile_size * 4].copy_from_slice(pixels_line);
+                }
+
+                let color = match cell {
+                    _ if cell.has_box() => box_color,
+                    _ if cell.is_goal() && !cell.has_sokoban() => goal_color,
+                    _ => 0,
+                };
+                if let Some(mark) = (color as usize).checked_sub(1).and_then(|index| tiles.color_marks.get(index)) {
+                    for line in mark_start..mark_start + mark_size {
+                        let start = (top + line) * row + (left + mark_start) * 4;
+                        pixels[start..start + mark_size * 4].chunks_mut(4).for_each(|p| p.copy_from_slice(mark));
+                    }
+                }
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    pub fn to_png(&self, tiles: &Tileset) -> Result<Vec<u8>, RenderError> {
+        let (width, height) = self.image_size(tiles)?;
+        let pixels = self.to_rgba(tiles)?;
+
+        let mut png_bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png_bytes, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+
+        Ok(png_bytes)
+    }
+
+    /// Animated GIF of `moves` played from `start_state` by the first sokoban, one
+    /// frame per move after the initial one. Hex boards take hex moves, others square
+    /// ones, see `BoardState::make_move`. Alpha is dropped, GIF frames are opaque.
+    pub fn replay_to_gif(
+        &self,
+        moves: &[Heading],
+        tiles: &Tileset,
+        options: ReplayOptions,
+    ) -> Result<Vec<u8>, RenderError> {
+        let (width, height) = self.image_size(tiles)?;
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(RenderError::TooLarge),
+        };
+
+        // Tiles usually have few colours, which then make a global palette and exact
+        // frames. Otherwise every frame is quantized on its own.
+        let palette = tiles.palette();
+        let global_palette: Vec<u8> = match &palette {
+            Some(colors) => {
+                let mut entries = vec![[0u8; 3]; colors.len()];
+                for (color, &index) in colors {
+                    entries[index as usize] = *color;
+                }
+                entries.concat()
+            },
+            None => Vec::new(),
+        };
+
+        let mut gif_bytes = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut gif_bytes, width, height, &global_palette)?;
+            if options.repeat {
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+            }
+
+            let mut state = self.start_state()?;
+            for frame_index in 0..=moves.len() {
+                if frame_index > 0 {
+                    state.make_move(self, 0, moves[frame_index - 1]);
+                }
+
+                let mut pixels = self.with_state(&state).to_rgba(tiles)?;
+                let mut frame = match &palette {
+                    Some(colors) => {
+                        let indices = pixels.chunks(4).map(|p| colors[&[p[0], p[1], p[2]]]).collect::<Vec<u8>>();
+                        gif::Frame::from_indexed_pixels(width, height, indices, None)
+                    },
+                    None => {
+                        pixels.chunks_mut(4).for_each(|p| p[3] = 255);
+                        gif::Frame::from_rgba_speed(width, height, &mut pixels, 10)
+                    },
+                };
+
+                frame.delay = if frame_index == moves.len() { options.final_delay } else { options.frame_delay };
+                encoder.write_frame(&frame)?;
+            }
+        }
+
+        Ok(gif_bytes)
+    }
+
+    fn image_size(&self, tiles: &Tileset) -> Result<(u32, u32), RenderError> {
+        let (columns, rows) = self.drawn_size();
+        let width = drawn_left(columns, tiles.tile_size as usize) as u64;
+        let height = rows as u64 * tiles.tile_size as u64;
+        match (u32::try_from(width), u32::try_from(height)) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+            _ => Err(RenderError::TooLarge),
+        }
+    }
+}
+
+/// Pixels from the left of the half cell `column`, see `Board::drawn_at`.
+fn drawn_left(column: usize, tile_size: usize) -> usize {
+    column / 2 * tile_size + column % 2 * (tile_size / 2)
+}
+
+/// Parses the `#rrggbb` colours of a `Palette`.
+fn parse_color(hex: &str) -> [u8; 4] {
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).expect("Palette colours are #rrggbb");
+    [channel(1), channel(3), channel(5), 255]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = "#####\n#@$.#\n#####";
+
+    fn decode_png(bytes: &[u8]) -> (png::OutputInfo, Vec<u8>) {
+        let mut reader = png::Decoder::new(bytes).read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).unwrap();
+        (info, buffer)
+    }
+
+    fn pixel(pixels: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let start = ((y * width + x) * 4) as usize;
+        pixels[start..start + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_png_uses_flat_tiles() {
+        let board = Board::from_xsb(LEVEL).unwrap();
+        let tiles = Tileset::flat(8, Theme::Classic);
+        let (info, pixels) = decode_png(&board.to_png(&tiles).unwrap());
+
+        assert_eq!((info.width, info.height), (40, 24));
+        let palette = Theme::Classic.palette();
+        // Centres of a wall, the sokoban and the box.
+        assert_eq!(pixel(&pixels, 40, 4, 4), parse_color(palette.wall));
+        assert_eq!(pixel(&pixels, 40, 12, 12), parse_color(palette.sokoban));
+        assert_eq!(pixel(&pixels, 40, 20, 12), parse_color(palette.box_fill));
+    }
+
+    #[test]
+    fn test_png_marks_colors_and_misplaced_boxes() {
+        let board = Board::from_xsb("#@$.*#")
+            .unwrap()
+            .with_colors(vec![(Point { x: 2, y: 0 }, 1)], vec![(Point { x: 3, y: 0 }, 1), (Point { x: 4, y: 0 }, 2)]);
+        let tiles = Tileset::flat(8, Theme::Classic);
+        let pixels = board.to_rgba(&tiles).unwrap();
+        let palette = Theme::Classic.palette();
+
+        // Middles of the box and goal of colour 1, then the plain box on the goal of colour 2.
+        assert_eq!(pixel(&pixels, 48, 20, 4), parse_color(palette.colors[0]));
+        assert_eq!(pixel(&pixels, 48, 28, 4), parse_color(palette.colors[0]));
+        assert_eq!(pixel(&pixels, 48, 36, 4), parse_color(palette.box_fill));
+    }
+
+    #[test]
+    fn test_sprite_sheet() {
+        // One pixel tiles, each of a shade that tells its state.
+        let sheet: Vec<u8> = (0..MIN_TILES as u8).flat_map(|state| [state * 10, 0, 0]).collect();
+        let mut sheet_png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut sheet_png, MIN_TILES as u32, 1);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header().unwrap().write_image_data(&sheet).unwrap();
+
+        let tiles = Tileset::from_sprite_sheet(&sheet_png).unwrap();
+        assert_eq!(tiles.tile_size(), 1);
+
+        let board = Board::from_xsb(LEVEL).unwrap();
+        let pixels = board.to_rgba(&tiles).unwrap();
+        assert_eq!(pixel(&pixels, 5, 1, 1), [40, 0, 0, 255]);
+        assert_eq!(pixel(&pixels, 5, 3, 1), [60, 0, 0, 255]);
+
+        assert!(matches!(Tileset::from_sprite_sheet(&board.to_png(&tiles).unwrap()), Err(RenderError::InvalidSprites(_))));
+
+        let ice = Board::from_xsb("#@$~.#").unwrap();
+        assert_eq!(ice.to_rgba(&tiles), Err(SokobanError::UnsupportedCell(Cell::Ice)));
+        assert!(ice.to_rgba(&Tileset::default()).is_ok());
+    }
+
+    #[test]
+    fn test_gif_replay_has_a_frame_per_move() {
+        let board = Board::from_xsb(LEVEL).unwrap();
+        let moves = [Direction::Forward, Direction::Forward, Direction::Up].map(Heading::from);
+        let gif_bytes = board.replay_to_gif(&moves, &Tileset::flat(4, Theme::Dark), ReplayOptions::default()).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(gif_bytes.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (20, 12));
+
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, frame.buffer.to_vec()));
+        }
+
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].0, 20);
+        assert_eq!(frames[3].0, 150);
+        // The sokoban moved once, the box is blocked by the wall, and up is a wall.
+        assert_ne!(frames[0].1, frames[1].1);
+        assert_eq!(frames[1].1, frames[3].1);
+        let expected = board.make_step(Direction::Forward).unwrap().to_rgba(&Tileset::flat(4, Theme::Dark)).unwrap();
+        assert_eq!(frames[3].1, expected);
+    }
+
+    #[test]
+    fn test_gif_replay_starts_from_the_start_state() {
+        let board = Board::from_xsb("#@ $.#").unwrap().with_ruleset(Ruleset::Reverse);
+        let tiles = Tileset::flat(4, Theme::Dark);
+        let gif_bytes = board.replay_to_gif(&[], &tiles, ReplayOptions::default()).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(gif_bytes.as_slice()).unwrap();
+        let frame = decoder.read_next_frame().unwrap().unwrap().buffer.to_vec();
+
+        let start = board.with_state(&board.start_state().unwrap());
+        assert_eq!(frame, start.to_rgba(&tiles).unwrap());
+        assert_ne!(frame, board.to_rgba(&tiles).unwrap());
+    }
+
+    #[test]
+    fn test_hex_replay_shifts_odd_rows() {
+        let board = Board::from_hex_xsb("# # # #\n # @ $ #\n# # . #").unwrap();
+        let tiles = Tileset::flat(4, Theme::Dark);
+        let moves = [Heading::Hex(HexDirection::SouthEast)];
+        let gif_bytes = board.replay_to_gif(&moves, &tiles, ReplayOptions::default()).unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(gif_bytes.as_slice()).unwrap();
+        // Four tiles a row and half a tile for the shifted rows.
+        assert_eq!((decoder.width(), decoder.height()), (18, 12));
+
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push(frame.buffer.to_vec());
+        }
+        let mut state = board.start_state().unwrap();
+        assert!(state.make_hex_step(&board, 0, HexDirection::SouthEast));
+        assert_eq!(frames[1], board.with_state(&state).to_rgba(&tiles).unwrap());
+        assert_ne!(frames[0], frames[1]);
+
+        // The sokoban's tile starts half a tile in, at the second cell of the second row.
+        let sokoban = tiles.tile(Cell::Sokoban).unwrap();
+        let pixels = board.to_rgba(&tiles).unwrap();
+        let start = (4 * 18 + 6) * 4;
+        assert_eq!(&pixels[start..start + 16], &sokoban[..16]);
+    }
+}
@@ -0,0 +1,382 @@
+use std::fmt::Write;
+
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::auxiliary::*;
+use crate::board::*;
+use crate::cell::*;
+use crate::error::*;
+
+/// Colour theme of a rendered board.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "borsh", derive(BorshDeserialize, BorshSerialize))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Theme {
+    #[default]
+    Classic,
+    Dark,
+    /// Black and white with a yellow sokoban, for small screens and projectors.
+    HighContrast,
+}
+
+/// Fill colours used by a `Theme`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Palette {
+    pub wall: &'static str,
+    pub floor: &'static str,
+    pub goal: &'static str,
+    pub box_fill: &'static str,
+    pub box_on_goal: &'static str,
+    pub sokoban: &'static str,
+    pub ice: &'static str,
+    pub teleporter: &'static str,
+    /// One-way arrows, pressure plates and gates.
+    pub mechanism: &'static str,
+    /// Marks of box and goal colours 1 and up. Colour 0 is plain and unmarked.
+    pub colors: [&'static str; MAX_COLORS as usize - 1],
+}
+
+impl Palette {
+    /// The mark of box or goal colour `color`, none for plain ones.
+    pub fn color_mark(&self, color: u8) -> Option<&'static str> {
+        self.colors.get((color as usize).checked_sub(1)?).copied()
+    }
+}
+
+impl Theme {
+    pub fn palette(&self) -> Palette {
+        match self {
+            Theme::Classic => Palette {
+                wall: "#77491c",
+                floor: "#f2e6cf",
+                goal: "#d9534f",
+                box_fill: "#ffb300",
+                box_on_goal: "#5cb85c",
+                sokoban: "#2a6fdb",
+                ice: "#bfe6f5",
+                teleporter: "#8e44ad",
+                mechanism: "#4d4d4d",
+                colors: ["#c0392b", "#1abc9c", "#34495e"],
+            },
+            Theme::Dark => Palette {
+                wall: "#1e1f26",
+                floor: "#3b3d4a",
+                goal: "#ff6b6b",
+                box_fill: "#c8963e",
+                box_on_goal: "#4cd137",
+                sokoban: "#70a1ff",
+                ice: "#4a6b82",
+                teleporter: "#c56cf0",
+                mechanism: "#a4b0be",
+                colors: ["#ff7979", "#7bed9f", "#eccc68"],
+            },
+            Theme::HighContrast => Palette {
+                wall: "#000000",
+                floor: "#ffffff",
+                goal: "#000000",
+                box_fill: "#7f7f7f",
+                box_on_goal: "#000000",
+                sokoban: "#ffd400",
+                ice: "#c8c8c8",
+                teleporter: "#0050ff",
+                mechanism: "#ff0000",
+                colors: ["#e00000", "#00a000", "#0000e0"],
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SvgOptions {
+    /// Side of a cell in pixels.
+    pub cell_size: u32,
+    pub theme: Theme,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self { cell_size: 32, theme: Theme::default() }
+    }
+}
+
+impl Board {
+    /// Draws the board as a standalone SVG document. Walls and floor are merged into
+    /// one rectangle per run of equal cells, so the output stays small on large boards.
+    /// Cells are placed by `drawn_at`.
+    pub fn to_svg(&self, options: &SvgOptions) -> Result<String, SokobanError> {
+        let palette = options.theme.palette();
+        let cell = options.cell_size as usize;
+        let half = cell as f32 / 2.0;
+        let (columns, rows) = self.drawn_size();
+        let width = (columns * cell).div_ceil(2);
+        let height = rows * cell;
+
+        let mut svg = String::new();
+        // Writing to a `String` never fails.
+        let _ = write!(
+            svg,
+            "<svg xmlns='http://www.w3.org/2000/svg' width='{w}' height='{h}' viewBox='0 0 {w} {h}'>",
+            w = width,
+            h = height,
+        );
+
+        let _ = write!(svg, "<g shape-rendering='crispEdges'>");
+        for y in 0..self.size.height {
+            let mut x = 0;
+            while x < self.size.width {
+                let Some((column, row)) = self.drawn_at(Point { x, y }) else {
+                    x += 1;
+                    continue;
+                };
+                let is_wall = self.is_wall(Point { x, y });
+                let start = x;
+                while x < self.size.width
+                    && self.drawn_at(Point { x, y }).is_some()
+                    && self.is_wall(Point { x, y }) == is_wall
+                {
+                    x += 1;
+                }
+
+                let fill = if is_wall { palette.wall } else { palette.floor };
+                let _ = write!(
+                    svg,
+                    "<rect x='{}' y='{}' width='{}' height='{}' fill='{}'/>",
+                    column as f32 * half,
+                    row * cell,
+                    (x - start) * cell,
+                    cell,
+                    fill,
+                );
+            }
+        }
+        let _ = write!(svg, "</g>");
+
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let Some((column, row)) = self.drawn_at(Point { x, y }) else {
+                    continue;
+                };
+                let cell_kind = self.get_cell(Point { x, y }).unwrap();
+                let (left, top) = (column as f32 * half, (row * cell) as f32);
+                let (cx, cy) = (left + half, top + half);
+
+                if cell_kind.is_ice() {
+                    let _ = write!(
+                        svg,
+                        "<rect x='{}' y='{}' width='{c}' height='{c}' fill='{}' shape-rendering='crispEdges'/>",
+                        left,
+                        top,
+                        palette.ice,
+                        c = cell,
+                    );
+                }
+                if cell_kind.is_exit() {
+                    let _ = write!(
+                        svg,
+                        "<rect x='{}' y='{}' width='{c}' height='{c}' fill='{}' fill-opacity='0.3' shape-rendering='crispEdges'/>",
+                        left,
+                        top,
+                        palette.goal,
+                        c = cell,
+                    );
+                }
+                if cell_kind.is_plate() {
+                    let inset = cell as f32 * 0.2;
+                    let _ = write!(
+                        svg,
+                        "<rect x='{}' y='{}' width='{s}' height='{s}' fill='none' stroke='{}' stroke-width='{}'/>",
+                        left + inset,
+                        top + inset,
+                        palette.mechanism,
+                        cell as f32 * 0.06,
+                        s = cell as f32 - 2.0 * inset,
+                    );
+                }
+                if cell_kind == Cell::Gate {
+                    let _ = write!(
+                        svg,
+                        "<rect x='{}' y='{}' width='{c}' height='{c}' fill='{}' shape-rendering='crispEdges'/>",
+                        left,
+                        top,
+                        palette.mechanism,
+                        c = cell,
+                    );
+                } else if cell_kind.is_gate() {
+                    let _ = write!(
+                        svg,
+                        "<rect x='{}' y='{}' width='{s}' height='{s}' fill='none' stroke='{}' stroke-width='{w}' stroke-dasharray='{w}'/>",
+                        left + cell as f32 * 0.05,
+                        top + cell as f32 * 0.05,
+                        palette.mechanism,
+                        s = cell as f32 * 0.9,
+                        w = cell as f32 * 0.1,
+                    );
+                }
+                if let Some(direction) = self.one_way_direction(Point { x, y }) {
+                    let (dx, dy) = match direction {
+                        Direction::Backward => (-1.0, 0.0),
+                        Direction::Forward => (1.0, 0.0),
+                        Direction::Up => (0.0, -1.0),
+                        Direction::Down => (0.0, 1.0),
+                    };
+                    // A triangle pointing in `direction`, its base across the cell.
+                    let (tip, back, side) = (half * 0.6, half * 0.4, half * 0.5);
+                    let _ = write!(
+                        svg,
+                        "<polygon points='{},{} {},{} {},{}' fill='{}' fill-opacity='0.5'/>",
+                        cx + dx * tip,
+                        cy + dy * tip,
+                        cx - dx * back - dy * side,
+                        cy - dy * back - dx * side,
+                        cx - dx * back + dy * side,
+                        cy - dy * back + dx * side,
+                        palette.mechanism,
+                    );
+                }
+                if cell_kind.is_teleporter() {
+                    let _ = write!(
+                        svg,
+                        "<circle cx='{}' cy='{}' r='{}' fill='none' stroke='{}' stroke-width='{}' stroke-dasharray='{}'/>",
+                        cx,
+                        cy,
+                        half * 0.8,
+                        palette.teleporter,
+                        cell as f32 * 0.08,
+                        cell as f32 * 0.15,
+                    );
+                }
+                let box_color = self.box_color(Point { x, y });
+                let goal_color = self.goal_color(Point { x, y });
+                if cell_kind.is_goal() && !cell_kind.has_box() {
+                    let _ = write!(
+                        svg,
+                        "<circle cx='{}' cy='{}' r='{}' fill='none' stroke='{}' stroke-width='{}'/>",
+                        cx,
+                        cy,
+                        half * 0.4,
+                        palette.color_mark(goal_color).unwrap_or(palette.goal),
+                        cell as f32 * 0.08,
+                    );
+                }
+                if cell_kind.has_box() {
+                    // A box on a goal of another colour isn't placed.
+                    let placed = cell_kind.is_goal() && box_color == goal_color;
+                    let fill = if placed { palette.box_on_goal } else { palette.box_fill };
+                    let inset = cell as f32 * 0.1;
+                    let _ = write!(
+                        svg,
+                        "<rect x='{}' y='{}' width='{s}' height='{s}' rx='{r}' fill='{}' stroke='{}' stroke-width='{r}'/>",
+                        left + inset,
+                        top + inset,
+                        fill,
+                        palette.wall,
+                        s = cell as f32 - 2.0 * inset,
+                        r = cell as f32 * 0.06,
+                    );
+                    if let Some(mark) = palette.color_mark(box_color) {
+                        let _ = write!(svg, "<circle cx='{}' cy='{}' r='{}' fill='{}'/>", cx, cy, half * 0.3, mark);
+                    }
+                }
+                if cell_kind.has_sokoban() {
+                    let _ = write!(svg, "<circle cx='{}' cy='{}' r='{}' fill='{}'/>", cx, cy, half * 0.7, palette.sokoban);
+                }
+            }
+        }
+
+        svg.push_str("</svg>");
+        Ok(svg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_svg_draws_every_piece() {
+        let board = Board::from_xsb("#####\n#@$.#\n# *+#\n#####").unwrap();
+        let svg = board.to_svg(&SvgOptions::default()).unwrap();
+
+        assert!(svg.starts_with("<svg xmlns='http://www.w3.org/2000/svg' width='160' height='128' viewBox='0 0 160 128'>"));
+        assert!(svg.ends_with("</svg>"));
+        // The top and bottom rows are a single wall each, the middle rows wall, floor, wall.
+        assert_eq!(svg.matches("fill='#77491c'/>").count(), 1 + 2 + 2 + 1);
+        assert_eq!(svg.matches("fill='#f2e6cf'/>").count(), 2);
+        // A box and a box on goal.
+        assert_eq!(svg.matches("rx=").count(), 2);
+        // The sokoban twice (alone and on a goal), the goal twice.
+        assert_eq!(svg.matches("fill='#2a6fdb'").count(), 2);
+        assert_eq!(svg.matches("stroke='#d9534f'").count(), 2);
+    }
+
+    #[test]
+    fn test_svg_draws_teleporters_under_pieces() {
+        let board = Board::from_xsb("#@$^ ^.#").unwrap();
+        let svg = board.make_step(Direction::Forward).unwrap().to_svg(&SvgOptions::default()).unwrap();
+
+        assert_eq!(svg.matches("stroke='#8e44ad'").count(), 2);
+        assert!(svg.find("stroke='#8e44ad'").unwrap() < svg.find("rx=").unwrap());
+    }
+
+    #[test]
+    fn test_svg_draws_one_way_arrows_and_gates() {
+        let mut board = Board::new_with_encoding(Size { width: 5, height: 1 }, CellEncoding::Extended);
+        let cells = [Cell::Sokoban, Cell::OneWay, Cell::Plate, Cell::Gate, Cell::Floor];
+        for (x, cell) in cells.into_iter().enumerate() {
+            board.set_cell(Point { x, y: 0 }, cell).unwrap();
+        }
+        let board = board
+            .with_one_way(vec![(Point { x: 1, y: 0 }, Direction::Up)])
+            .with_gates(vec![(Point { x: 2, y: 0 }, Point { x: 3, y: 0 })]);
+        let svg = board.to_svg(&SvgOptions { cell_size: 10, theme: Theme::Classic }).unwrap();
+
+        // The arrow points up from the middle of its cell.
+        assert!(svg.contains("<polygon points='15,2 17.5,7 12.5,7'"));
+        assert_eq!(svg.matches("stroke='#4d4d4d'").count(), 1);
+        assert!(svg.contains("<rect x='30' y='0' width='10' height='10' fill='#4d4d4d'"));
+    }
+
+    #[test]
+    fn test_svg_marks_colors_and_misplaced_boxes() {
+        let board = Board::from_xsb("#@$.*#")
+            .unwrap()
+            .with_colors(vec![(Point { x: 2, y: 0 }, 1)], vec![(Point { x: 3, y: 0 }, 1), (Point { x: 4, y: 0 }, 2)]);
+        let svg = board.to_svg(&SvgOptions::default()).unwrap();
+        let palette = Theme::Classic.palette();
+
+        // The box of colour 1 and the goal of colour 1 are marked, the plain box
+        // on the goal of colour 2 isn't placed.
+        assert!(svg.contains(&format!("r='4.8' fill='{}'", palette.colors[0])));
+        assert!(svg.contains(&format!("stroke='{}'", palette.colors[0])));
+        assert_eq!(svg.matches(&format!("fill='{}'", palette.box_fill)).count(), 2);
+        assert!(!svg.contains(palette.box_on_goal));
+    }
+
+    #[test]
+    fn test_svg_draws_hex_rows_shifted() {
+        let board = Board::from_hex_xsb("# # # #\n # @ $ #\n# # . #").unwrap();
+        let svg = board.to_svg(&SvgOptions { cell_size: 10, theme: Theme::Classic }).unwrap();
+
+        // Four cells a row and half a cell for the shifted rows, like the hex notation.
+        assert!(svg.contains("width='45' height='30'"));
+        // The walls beside the level aren't drawn: the first row is a single wall.
+        assert!(svg.contains("<rect x='0' y='0' width='40' height='10' fill='#77491c'/>"));
+        // The sokoban in the second row is half a cell to the right.
+        assert!(svg.contains("<circle cx='20' cy='15' r='3.5' fill='#2a6fdb'/>"));
+    }
+
+    #[test]
+    fn test_svg_themes_and_cell_size() {
+        let board = Board::from_xsb("#@$.#").unwrap();
+        let options = SvgOptions { cell_size: 10, theme: Theme::Dark };
+        let svg = board.to_svg(&options).unwrap();
+
+        assert!(svg.contains("width='50' height='10'"));
+        assert!(svg.contains(Theme::Dark.palette().wall));
+        assert!(!svg.contains(Theme::Classic.palette().wall));
+    }
+}
@@ -0,0 +1,412 @@
+use crate::auxiliary::*;
+use crate::board::*;
+use crate::cell::*;
+use crate::error::*;
+
+const BOXES_PREFIX: &str = "boxes:";
+const GOALS_PREFIX: &str = "goals:";
+
+impl Board {
+    /// Parses a level in the common XSB notation. Rows may have different widths,
+    /// missing cells are treated as walls.
+    ///
+    /// Ice is written `~`, a box on ice `=` and the sokoban on ice `&`. A level with
+    /// ice uses the extended encoding and `Ruleset::Ice`.
+    ///
+    /// Teleporters are written `^`, with a box on them `%` and with the sokoban `!`.
+    /// They are paired in reading order: the first with the second, the third with
+    /// the fourth, and so on.
+    ///
+    /// Coloured levels list the colours of their boxes and goals in reading order
+    /// on lines after the grid, such as `boxes: 1 0` and `goals: 0 1`. Levels
+    /// without these lines only have plain boxes and goals.
+    pub fn from_xsb(text: &str) -> Result<Self, SokobanError> {
+        let (color_lines, rows): (Vec<&str>, Vec<&str>) = text
+            .lines()
+            .map(|line| line.trim_end_matches(['\r', '\n']))
+            .filter(|line| !line.trim().is_empty())
+            .partition(|line| line.starts_with(BOXES_PREFIX) || line.starts_with(GOALS_PREFIX));
+
+        let cells = rows
+            .iter()
+            .map(|row| row.chars().map(Self::xsb_symbol_as_cell).collect::<Result<Vec<_>, _>>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let is_classic = cells.iter().flatten().all(|cell| cell.is_classic());
+        let encoding = if is_classic { CellEncoding::Classic } else { CellEncoding::Extended };
+        let has_ice = cells.iter().flatten().any(|cell| cell.is_ice());
+        let ruleset = if has_ice { Ruleset::Ice } else { Ruleset::Classic };
+
+        let width = cells.iter().map(Vec::len).max().unwrap_or(0);
+        let mut board = Board::new_with_encoding(Size { width, height: rows.len() }, encoding);
+
+        for (y, row) in cells.into_iter().enumerate() {
+            for (x, cell) in row.into_iter().enumerate() {
+                board.set_cell(Point { x, y }, cell)?;
+            }
+        }
+
+        let teleporters = board.points_in_reading_order(Cell::is_teleporter);
+        let pairs = teleporters.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect();
+
+        let mut box_colors = Vec::new();
+        let mut goal_colors = Vec::new();
+        for line in color_lines {
+            if let Some(colors) = line.strip_prefix(BOXES_PREFIX) {
+                box_colors = Self::parse_xsb_colors(colors, board.points_in_reading_order(Cell::has_box))?;
+            } else if let Some(colors) = line.strip_prefix(GOALS_PREFIX) {
+                goal_colors = Self::parse_xsb_colors(colors, board.points_in_reading_order(Cell::is_goal))?;
+            }
+        }
+
+        // An odd teleporter is left without a partner, which makes the board invalid.
+        let board = Board { ruleset, teleporters: pairs, ..board };
+        Ok(board.with_colors(box_colors, goal_colors))
+    }
+
+    /// Writes the board in the notation of `from_xsb`. Floor at the end of a row is
+    /// written `-`, so it isn't lost as trailing whitespace.
+    pub fn to_xsb(&self) -> Result<String, SokobanError> {
+        let mut rows = Vec::new();
+
+        for y in 0..self.size.height {
+            let mut symbols = Vec::new();
+            for x in 0..self.size.width {
+                let cell = self.get_cell(Point { x, y }).unwrap();
+                symbols.push(Self::cell_as_xsb_symbol(cell)?.to_string());
+            }
+            rows.push(mark_trailing_floor(symbols).concat());
+        }
+
+        if !self.box_colors.is_empty() {
+            let colors = self.points_in_reading_order(Cell::has_box).into_iter().map(|cord| self.box_color(cord));
+            rows.push(Self::xsb_colors_line(BOXES_PREFIX, colors));
+        }
+        if !self.goal_colors.is_empty() {
+            let colors = self.points_in_reading_order(Cell::is_goal).into_iter().map(|cord| self.goal_color(cord));
+            rows.push(Self::xsb_colors_line(GOALS_PREFIX, colors));
+        }
+
+        Ok(rows.join("\n"))
+    }
+
+    /// Parses a level in hex notation, for `Topology::Hex`. The cells of a row are
+    /// written two characters apart and every other row is shifted by one
+    /// character, so a cell touches two cells of the row above and two of the row
+    /// below. Symbols and colour lines are the ones of `from_xsb`.
+    ///
+    /// The field holds the level in axial coordinates: rows are kept and each
+    /// pair of rows starts one cell further left, so the field is wider than the
+    /// level by half its height. Cells the level doesn't reach are walls. A level
+    /// whose first row is indented gets a wall in front of every other row, so it
+    /// reads like one whose first row isn't.
+    pub fn from_hex_xsb(text: &str) -> Result<Self, SokobanError> {
+        let (color_lines, rows): (Vec<&str>, Vec<&str>) = text
+            .lines()
+            .map(|line| line.trim_end_matches(['\r', '\n']))
+            .filter(|line| !line.trim().is_empty())
+            .partition(|line| line.starts_with(BOXES_PREFIX) || line.starts_with(GOALS_PREFIX));
+
+        // Rows alternate between cells on even and on odd columns, starting with the first row.
+        let first_parity = rows.first().and_then(|row| row.find(|symbol| symbol != ' ')).unwrap_or(0) % 2;
+        let mut cells = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            let mut row_cells = String::new();
+            if first_parity == 1 && y % 2 == 0 {
+                row_cells.push('#');
+            }
+            for (column, symbol) in row.chars().enumerate() {
+                if column % 2 == (first_parity + y) % 2 {
+                    row_cells.push(symbol);
+                } else if symbol != ' ' {
+                    return Err(SokobanError::UnknownSymbol(symbol));
+                }
+            }
+            cells.push(row_cells);
+        }
+
+        let shift = rows.len().saturating_sub(1) / 2;
+        let columns = cells.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        let mut square_rows: Vec<String> = cells
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                let padding = columns - row.chars().count();
+                format!("{}{}{}", "#".repeat(shift - y / 2), row, "#".repeat(padding))
+            })
+            .collect();
+        square_rows.extend(color_lines.into_iter().map(String::from));
+
+        Ok(Board::from_xsb(&square_rows.join("\n"))?.with_topology(Topology::Hex))
+    }
+
+    /// Writes a hex board in the notation of `from_hex_xsb`. Fails on cells beside
+    /// the level, which the notation can't reach. Floor at the end of a row is
+    /// written `-`, as in `to_xsb`.
+    pub fn to_hex_xsb(&self) -> Result<String, SokobanError> {
+        let (shift, columns) = self.hex_columns();
+        let mut rows = Vec::new();
+
+        for y in 0..self.size.height {
+            let first = shift - y / 2;
+            let mut symbols = Vec::new();
+            for x in 0..self.size.width {
+                let cell = self.get_cell(Point { x, y }).unwrap();
+                if (first..first + columns).contains(&x) {
+                    symbols.push(Self::cell_as_xsb_symbol(cell)?.to_string());
+                } else if !cell.is_wall() {
+                    return Err(SokobanError::UnsupportedCell(cell));
+                }
+            }
+            let indent = if y % 2 == 1 { " " } else { "" };
+            rows.push(format!("{}{}", indent, mark_trailing_floor(symbols).join(" ")));
+        }
+
+        // The colour lines of the square notation, in the same reading order.
+        let square = self.to_xsb()?;
+        rows.extend(square.lines().skip(self.size.height).map(String::from));
+        Ok(rows.join("\n"))
+    }
+
+    fn points_in_reading_order(&self, predicate: fn(Cell) -> bool) -> Vec<Point> {
+        let mut points = Vec::new();
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                if self.get_cell(Point { x, y }).is_some_and(predicate) {
+                    points.push(Point { x, y });
+                }
+            }
+        }
+        points
+    }
+
+    /// Pairs the colours written in `text` with `points`, one colour per point.
+    fn parse_xsb_colors(text: &str, points: Vec<Point>) -> Result<Vec<(Point, u8)>, SokobanError> {
+        let colors = text
+            .split_whitespace()
+            .map(|color| match color.parse::<u8>() {
+                Ok(color) if color < MAX_COLORS => Ok(color),
+                Ok(color) => Err(SokobanError::UnknownColor(color)),
+                Err(_) => Err(SokobanError::UnknownSymbol(color.chars().next().unwrap_or(' '))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if colors.len() != points.len() {
+            return Err(SokobanError::ColorCountMismatch);
+        }
+        Ok(points.into_iter().zip(colors).collect())
+    }
+
+    fn xsb_colors_line(prefix: &str, colors: impl Iterator<Item = u8>) -> String {
+        let colors: Vec<String> = colors.map(|color| color.to_string()).collect();
+        format!("{} {}", prefix, colors.join(" "))
+    }
+
+    pub fn xsb_symbol_as_cell(symbol: char) -> Result<Cell, SokobanError> {
+        match symbol {
+            '#' => Ok(Cell::Wall),
+            ' ' | '-' | '_' => Ok(Cell::Floor),
+            '$' => Ok(Cell::Box),
+            '*' => Ok(Cell::BoxOnGoal),
+            '@' => Ok(Cell::Sokoban),
+            '+' => Ok(Cell::SokobanOnGoal),
+            '.' => Ok(Cell::Goal),
+            '~' => Ok(Cell::Ice),
+            '=' => Ok(Cell::BoxOnIce),
+            '&' => Ok(Cell::SokobanOnIce),
+            '^' => Ok(Cell::Teleporter),
+            '%' => Ok(Cell::BoxOnTeleporter),
+            '!' => Ok(Cell::SokobanOnTeleporter),
+            _ => Err(SokobanError::UnknownSymbol(symbol)),
+        }
+    }
+
+    /// One-way cells, plates and gates need settings the notation can't hold.
+    pub fn cell_as_xsb_symbol(cell: Cell) -> Result<char, SokobanError> {
+        let symbol = match cell {
+            Cell::Wall => '#',
+            Cell::Floor => ' ',
+            Cell::Box => '$',
+            Cell::BoxOnGoal => '*',
+            Cell::Sokoban => '@',
+            Cell::SokobanOnGoal => '+',
+            Cell::Goal => '.',
+            Cell::Ice => '~',
+            Cell::BoxOnIce => '=',
+            Cell::SokobanOnIce => '&',
+            Cell::Teleporter => '^',
+            Cell::BoxOnTeleporter => '%',
+            Cell::SokobanOnTeleporter => '!',
+            _ => return Err(SokobanError::UnsupportedCell(cell)),
+        };
+        Ok(symbol)
+    }
+}
+
+impl Direction {
+    /// LURD notation of a move: lowercase for walking, uppercase for pushing.
+    pub fn to_lurd(self, push: bool) -> char {
+        let symbol = match self {
+            Direction::Backward => 'l',
+            Direction::Up => 'u',
+            Direction::Forward => 'r',
+            Direction::Down => 'd',
+        };
+
+        if push { symbol.to_ascii_uppercase() } else { symbol }
+    }
+
+    pub fn from_lurd(symbol: char) -> Result<Self, SokobanError> {
+        match symbol.to_ascii_lowercase() {
+            'l' => Ok(Direction::Backward),
+            'u' => Ok(Direction::Up),
+            'r' => Ok(Direction::Forward),
+            'd' => Ok(Direction::Down),
+            _ => Err(SokobanError::UnknownSymbol(symbol)),
+        }
+    }
+}
+
+/// Writes the floor symbols at the end of a row as `-`.
+fn mark_trailing_floor(mut symbols: Vec<String>) -> Vec<String> {
+    for symbol in symbols.iter_mut().rev().take_while(|symbol| symbol.as_str() == " ") {
+        *symbol = String::from("-");
+    }
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = "\
+#####
+#@$.#
+# *+#
+#####";
+
+    #[test]
+    fn test_xsb_round_trip() {
+        let board = Board::from_xsb(LEVEL).unwrap();
+        assert!(!board.is_valid);
+
+        let level = LEVEL.replace('+', " ");
+        let board = Board::from_xsb(&level).unwrap();
+
+        assert!(board.is_valid);
+        assert_eq!(board.size, Size { width: 5, height: 4 });
+        assert_eq!(board.sokoban_position, Some(Point { x: 1, y: 1 }));
+        assert_eq!(board.to_xsb().unwrap(), level);
+    }
+
+    #[test]
+    fn test_xsb_keeps_floor_at_the_end_of_rows() {
+        let board = Board::from_xsb("#@$. ").unwrap();
+        let written = board.to_xsb().unwrap();
+
+        assert_eq!(written, "#@$.-");
+        assert_eq!(Board::from_xsb(&written).unwrap(), board);
+    }
+
+    #[test]
+    fn test_xsb_ragged_rows_are_padded_with_walls() {
+        let board = Board::from_xsb("####\n#@$.#\n#####\n").unwrap();
+
+        assert_eq!(board.size, Size { width: 5, height: 3 });
+        assert_eq!(board.get_state_at_cell(Point { x: 4, y: 0 }), Some(0));
+        assert!(board.is_valid);
+    }
+
+    #[test]
+    fn test_xsb_unknown_symbol() {
+        assert_eq!(Board::from_xsb("#@x#"), Err(SokobanError::UnknownSymbol('x')));
+    }
+
+    #[test]
+    fn test_xsb_teleporters_pair_in_reading_order() {
+        let level = "#@^$.^#\n#^ % .#\n#^#####";
+        let board = Board::from_xsb(level).unwrap();
+
+        assert_eq!(board.encoding, CellEncoding::Extended);
+        assert_eq!(board.teleporters, vec![
+            (Point { x: 2, y: 0 }, Point { x: 5, y: 0 }),
+            (Point { x: 1, y: 1 }, Point { x: 3, y: 1 }),
+        ]);
+        // The last teleporter has no partner.
+        assert!(!board.is_valid);
+        assert_eq!(board.to_xsb().unwrap(), level);
+    }
+
+    #[test]
+    fn test_xsb_colors_round_trip() {
+        let level = "########\n#@$*$..#\n########\nboxes: 2 0 1\ngoals: 0 1 2";
+        let board = Board::from_xsb(level).unwrap();
+
+        assert!(board.is_valid);
+        assert_eq!(board.box_colors, vec![(Point { x: 2, y: 1 }, 2), (Point { x: 4, y: 1 }, 1)]);
+        assert_eq!(board.goal_colors, vec![(Point { x: 5, y: 1 }, 1), (Point { x: 6, y: 1 }, 2)]);
+        assert_eq!(board.to_xsb().unwrap(), level);
+
+        assert_eq!(Board::from_xsb("#@$.#\nboxes: 1 1"), Err(SokobanError::ColorCountMismatch));
+        assert_eq!(Board::from_xsb("#@$.#\nboxes: 4"), Err(SokobanError::UnknownColor(4)));
+        assert_eq!(Board::from_xsb("#@$.#\ngoals: r"), Err(SokobanError::UnknownSymbol('r')));
+    }
+
+    #[test]
+    fn test_hex_xsb_round_trip() {
+        let level = "# # # # #\n # @ # # #\n# # $ # #\n # # . # #\n# # # # #";
+        let board = Board::from_hex_xsb(level).unwrap();
+
+        assert!(board.is_valid);
+        assert_eq!(board.topology, Topology::Hex);
+        assert_eq!(board.size, Size { width: 7, height: 5 });
+        assert_eq!(board.sokoban_position, Some(Point { x: 3, y: 1 }));
+        assert_eq!(board.get_cell(Point { x: 3, y: 3 }), Some(Cell::Goal));
+        assert_eq!(board.to_hex_xsb().unwrap(), level);
+
+        // The box lies south-east of the sokoban, as drawn.
+        let mut state = board.get_state().unwrap();
+        assert!(!state.make_step(&board, Direction::Down));
+        assert!(!state.make_hex_step(&board, 0, HexDirection::East));
+        assert!(state.make_hex_step(&board, 0, HexDirection::SouthEast));
+        assert!(state.is_solved(&board));
+
+        assert_eq!(Board::from_hex_xsb("#@#"), Err(SokobanError::UnknownSymbol('@')));
+    }
+
+    #[test]
+    fn test_hex_xsb_keeps_floor_at_the_end_of_rows() {
+        let board = Board::from_hex_xsb("# # # #\n # @ $  \n# # # #").unwrap();
+        let written = board.to_hex_xsb().unwrap();
+
+        assert_eq!(written, "# # # #\n # @ $ -\n# # # #");
+        assert_eq!(Board::from_hex_xsb(&written).unwrap(), board);
+    }
+
+    #[test]
+    fn test_hex_xsb_with_an_indented_first_row() {
+        let level = " # # # #\n# # @ # #\n # # $ #\n# # # . #\n # # # #";
+        let board = Board::from_hex_xsb(level).unwrap();
+        assert!(board.is_valid);
+
+        // The box lies south-east of the sokoban, as drawn.
+        let mut state = board.get_state().unwrap();
+        assert!(!state.make_hex_step(&board, 0, HexDirection::SouthWest));
+        assert!(state.make_hex_step(&board, 0, HexDirection::SouthEast));
+        assert!(state.is_solved(&board));
+
+        // Written back with the first row unindented, the level is the same.
+        let written = board.to_hex_xsb().unwrap();
+        assert_eq!(written, "# # # # #\n # # @ # #\n# # # $ #\n # # # . #\n# # # # #");
+        assert_eq!(Board::from_hex_xsb(&written).unwrap(), board);
+    }
+
+    #[test]
+    fn test_lurd() {
+        assert_eq!(Direction::Up.to_lurd(false), 'u');
+        assert_eq!(Direction::Backward.to_lurd(true), 'L');
+        assert_eq!(Direction::from_lurd('R'), Ok(Direction::Forward));
+        assert_eq!(Direction::from_lurd('d'), Ok(Direction::Down));
+        assert!(Direction::from_lurd('x').is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::auxiliary::*;
+use crate::board::*;
+use crate::error::*;
+
+// Zobrist keys are derived from the cell coordinates instead of a random table,
+// so they are the same for every board size and don't take any storage.
+const SOKOBAN_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+const BOX_SEED: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const COLOR_SEED: u64 = 0x1656_67B1_9E37_79F9;
+
+fn splitmix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn cell_key(seed: u64, cord: Point) -> u64 {
+    splitmix64(seed ^ ((cord.x as u64) << 32 | cord.y as u64))
+}
+
+pub fn sokoban_key(cord: Point) -> u64 {
+    cell_key(SOKOBAN_SEED, cord)
+}
+
+/// Key of the sokoban of `agent`. The first agent uses `sokoban_key`.
+pub fn agent_key(cord: Point, agent: usize) -> u64 {
+    cell_key(SOKOBAN_SEED.wrapping_add(agent as u64), cord)
+}
+
+/// Keys of the sokobans after the first, as kept in `BoardState::agents`.
+pub fn hash_agents(agents: &[Point]) -> u64 {
+    agents.iter().enumerate().fold(0, |hash, (index, cord)| hash ^ agent_key(*cord, index + 1))
+}
+
+pub fn box_key(cord: Point) -> u64 {
+    cell_key(BOX_SEED, cord)
+}
+
+/// Added to `box_key` for boxes that aren't plain, so swapping two boxes of
+/// different colours gives another hash.
+pub fn color_key(cord: Point, color: u8) -> u64 {
+    splitmix64(cell_key(COLOR_SEED, cord) ^ color as u64)
+}
+
+pub fn hash_colors(box_colors: &[(Point, u8)]) -> u64 {
+    box_colors.iter().fold(0, |hash, (cord, color)| hash ^ color_key(*cord, *color))
+}
+
+/// Hash of a position computed from scratch. `BoardState::make_step` keeps it
+/// up to date incrementally.
+pub fn hash_position(sokoban: Point, boxes: &[Point]) -> u64 {
+    boxes.iter().fold(sokoban_key(sokoban), |hash, cord| hash ^ box_key(*cord))
+}
+
+impl Board {
+    /// Transposition table key of the current position.
+    pub fn zobrist_hash(&self) -> Result<u64, SokobanError> {
+        Ok(self.get_state()?.hash)
+    }
+
+    /// Plays `moves` and finds the first loop in them: returns `(start, end)` such
+    /// that `moves[start..=end]` lead back to the position the board had before `start`.
+    pub fn find_repetition(&self, moves: &[Direction]) -> Result<Option<(usize, usize)>, SokobanError> {
+        let mut state = self.get_state()?;
+        let mut seen = vec![(state.hash, 0)];
+
+        for (index, direction) in moves.iter().enumerate() {
+            if !state.make_step(self, *direction) {
+                continue;
+            }
+
+            if let Some((_, start)) = seen.iter().find(|(hash, _)| *hash == state.hash) {
+                return Ok(Some((*start, index)));
+            }
+            seen.push((state.hash, index + 1));
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_board() -> Board {
+        let mut board = Board::new(Size { width: 4, height: 2 });
+
+        board.set_state_at_cell(Point { x: 0, y: 0 }, 4).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 0 }, 2).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 0 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 3, y: 0 }, 6).unwrap();
+        board.set_state_at_cell(Point { x: 0, y: 1 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 1, y: 1 }, 1).unwrap();
+        board.set_state_at_cell(Point { x: 2, y: 1 }, 1).unwrap();
+
+        board.validate_board()
+    }
+
+    #[test]
+    fn test_incremental_hash_matches_full_hash() {
+        let board = get_board();
+        let mut state = board.get_state().unwrap();
+
+        for direction in [Direction::Forward, Direction::Down, Direction::Backward, Direction::Up] {
+            state.make_step(&board, direction);
+            assert_eq!(state.hash, hash_position(state.sokoban, &state.boxes));
+            assert_eq!(state.hash, board.with_state(&state).zobrist_hash().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_box_and_sokoban_keys_differ() {
+        let cord = Point { x: 1, y: 0 };
+        assert_ne!(sokoban_key(cord), box_key(cord));
+        assert_ne!(sokoban_key(cord), sokoban_key(Point { x: 0, y: 1 }));
+    }
+
+    #[test]
+    fn test_find_repetition() {
+        let board = get_board();
+
+        let moves = [Direction::Down, Direction::Forward, Direction::Backward, Direction::Up];
+        assert_eq!(board.find_repetition(&moves), Ok(Some((1, 2))));
+
+        let moves = [Direction::Down, Direction::Up];
+        assert_eq!(board.find_repetition(&moves), Ok(Some((0, 1))));
+
+        let moves = [Direction::Forward, Direction::Forward];
+        assert_eq!(board.find_repetition(&moves), Ok(None));
+    }
+}